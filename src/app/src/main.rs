@@ -54,6 +54,7 @@ fn main() {
     let synth_state_receiver = sound_generator_factory.state_receiver.clone();
 
     let mut player = Player::new(sound_generator_factory, None);
+    player.set_master_limiter(true);
 
     let sample_rate = player.start().unwrap();
 
@@ -1,5 +1,6 @@
 use crossbeam::channel::{bounded, Sender};
 use dsp::core::StereoBuffer;
+use dsp::modules::dynamics::GainReductionMeter;
 
 use crate::{
     audioengine::AudioEngine,
@@ -30,6 +31,14 @@ where
         }
     }
 
+    pub fn set_master_limiter(&mut self, enabled: bool) {
+        self.synth_engine.set_master_limiter(enabled);
+    }
+
+    pub fn master_gain_reduction_meter(&self) -> GainReductionMeter {
+        self.synth_engine.master_gain_reduction_meter()
+    }
+
     pub fn start(&mut self) -> Result<f32, anyhow::Error> {
         let sample_rate = self.audio_engine.start()?;
         self.synth_engine.start(sample_rate);
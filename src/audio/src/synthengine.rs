@@ -1,5 +1,6 @@
 use crossbeam::channel::{unbounded, Receiver, Sender, TryRecvError};
 use dsp::core::{Module, StereoBuffer, StereoGenerator};
+use dsp::modules::dynamics::{GainReductionMeter, Limiter};
 use std::thread;
 
 enum SynthCommand {
@@ -21,6 +22,8 @@ where
     command_sender: Sender<SynthCommand>,
     command_receiver: Receiver<SynthCommand>,
     factory: T,
+    master_limiter: bool,
+    master_limiter_meter: GainReductionMeter,
 }
 
 impl<T> SynthEngine<T>
@@ -40,35 +43,60 @@ where
             command_sender,
             command_receiver,
             factory,
+            master_limiter: false,
+            master_limiter_meter: GainReductionMeter::new(),
         }
     }
 
+    // Safety brickwall limiter on the generator output, must be set before start.
+    pub fn set_master_limiter(&mut self, enabled: bool) {
+        self.master_limiter = enabled;
+    }
+
+    pub fn master_gain_reduction_meter(&self) -> GainReductionMeter {
+        self.master_limiter_meter.clone()
+    }
+
     pub fn start(&mut self, sample_rate: f32) {
         let audio_buffer_sender = self.audio_buffer_sender.clone();
         let command_receiver = self.command_receiver.clone();
 
         let factory = self.factory.clone();
         let aux_opt = self.auxiliary_audio_buffer_sender.clone();
+        let master_limiter = self.master_limiter;
+        let master_limiter_meter = self.master_limiter_meter.clone();
 
         thread::spawn(move || {
             // Create synth
             let mut stereo_generator = factory.create();
             stereo_generator.set_sample_rate(sample_rate);
+            let mut limiter = if master_limiter {
+                let mut limiter = Limiter::new();
+                limiter.set_sample_rate(sample_rate);
+                limiter.set_gain_reduction_meter(master_limiter_meter);
+                limiter.set_left_input(stereo_generator.get_left_output());
+                limiter.set_right_input(stereo_generator.get_right_output());
+                Some(limiter)
+            } else {
+                None
+            };
             // Play!
             loop {
                 match command_receiver.try_recv() {
                     Err(TryRecvError::Empty) => {
                         stereo_generator.process();
-                        let l = stereo_generator
-                            .get_left_output()
-                            .try_borrow()
-                            .unwrap()
-                            .clone_buffer();
-                        let r = stereo_generator
-                            .get_right_output()
-                            .try_borrow()
-                            .unwrap()
-                            .clone_buffer();
+                        let (left_output, right_output) = match limiter.as_mut() {
+                            Some(limiter) => {
+                                limiter.process();
+                                (limiter.get_left_output(), limiter.get_right_output())
+                            }
+                            None => (
+                                stereo_generator.get_left_output(),
+                                stereo_generator.get_right_output(),
+                            ),
+                        };
+                        let l = left_output.try_borrow().unwrap().clone_buffer();
+                        let r = right_output.try_borrow().unwrap().clone_buffer();
 
                         if let Some(aux) = aux_opt.as_ref() {
                             let _ = aux.send([l.clone_buffer(), r.clone_buffer()]);
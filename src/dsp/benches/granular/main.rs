@@ -0,0 +1,85 @@
+// Granulator::process with a pool of 256 grains kept full by a high density, against the
+// per sample engine it replaced.
+// cargo bench -p dsp --bench granular
+
+use std::time::{Duration, Instant};
+
+use dsp::core::{Module, SampleData, StereoGenerator};
+use dsp::modules::oscillators::Granulator;
+
+mod per_sample;
+use per_sample::PerSampleGranulator;
+
+const SAMPLE_RATE: f32 = 44_100.0;
+const POOL_SIZE: usize = 256;
+const GRAINS_PER_SEC: f32 = 2_000.0;
+// 100 samples attack and release, 0.5s sustain: about 1000 grains wanted, the pool stays full.
+const ATTACK_SLOPE: f32 = 0.01;
+const SUSTAIN_DURATION: f32 = 0.5 * SAMPLE_RATE;
+const RELEASE_SLOPE: f32 = -0.01;
+const PAN_SPREAD: f32 = 0.5;
+// Chunks processed by each run, 10 seconds of audio.
+const CHUNKS: usize = 10 * 44_100 / 32;
+const RUNS: usize = 5;
+
+fn samples() -> SampleData {
+    let len = 4 * SAMPLE_RATE as usize;
+    let left = (0..len).map(|i| (i as f32 * 0.01).sin()).collect();
+    let right = (0..len).map(|i| (i as f32 * 0.013).sin()).collect();
+    SampleData::new(vec![left, right])
+}
+
+// Fastest of the runs, after one second to fill the pool.
+fn measure<M: StereoGenerator>(module: &mut M, active_grains: impl Fn(&M) -> usize) -> Duration {
+    for _ in 0..SAMPLE_RATE as usize / 32 {
+        module.process();
+    }
+    assert_eq!(active_grains(module), POOL_SIZE);
+    let output = module.get_left_output();
+    let mut best = Duration::MAX;
+    let mut sum = 0.0;
+    for _ in 0..RUNS {
+        let now = Instant::now();
+        for _ in 0..CHUNKS {
+            module.process();
+            sum += output.borrow().get()[0];
+        }
+        best = best.min(now.elapsed());
+    }
+    // Keeps the output alive.
+    assert!(sum.is_finite());
+    best
+}
+
+fn report(name: &str, duration: Duration) {
+    let audio = CHUNKS as f64 * 32.0 / SAMPLE_RATE as f64;
+    println!(
+        "{:<12} {:>8.2} us/chunk {:>8.1}x realtime",
+        name,
+        duration.as_secs_f64() * 1e6 / CHUNKS as f64,
+        audio / duration.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut granulator = Granulator::new();
+    granulator.set_sample_rate(SAMPLE_RATE);
+    granulator.load_samples(samples());
+    granulator.set_grain_pool_size(POOL_SIZE);
+    granulator.set_grains_per_sec(GRAINS_PER_SEC);
+    granulator.set_pan_spread(PAN_SPREAD);
+    granulator.set_grain_attack_slope(ATTACK_SLOPE);
+    granulator.set_grain_sustain_duration(SUSTAIN_DURATION);
+    granulator.set_grain_release_slope(RELEASE_SLOPE);
+    let block = measure(&mut granulator, |g| g.active_grain_count());
+
+    let mut per_sample = PerSampleGranulator::new(samples(), POOL_SIZE, SAMPLE_RATE / GRAINS_PER_SEC);
+    per_sample.set_pan_spread(PAN_SPREAD);
+    per_sample.set_grain_envelope(ATTACK_SLOPE, SUSTAIN_DURATION, RELEASE_SLOPE);
+    let reference = measure(&mut per_sample, |g| g.active_grain_count());
+
+    println!("{} grains, {} grains/s", POOL_SIZE, GRAINS_PER_SEC);
+    report("block", block);
+    report("per sample", reference);
+    println!("speedup      {:>8.2}x", reference.as_secs_f64() / block.as_secs_f64());
+}
@@ -0,0 +1,243 @@
+use std::{cell::RefCell, rc::Rc};
+
+use dsp::core::{Buffer, Module, SampleData, SharedBuffer, StereoGenerator};
+use dsp::modules::oscillators::get_pan;
+
+// The baseline grain engine with a larger pool: every grain of the pool is visited and
+// rendered sample by sample. Reduced to the settings of the bench (trapezoid envelope,
+// synchronous scheduling, no random variations, wrapped region), reading both sides.
+
+#[derive(PartialEq)]
+enum GrainState {
+    Attack,
+    Sustain,
+    Release,
+    Stopped,
+}
+
+struct Grain {
+    position: f32,
+    state: GrainState,
+    l_coef: f32,
+    r_coef: f32,
+    attack_slope: f32,
+    sustain_time: f32,
+    release_slope: f32,
+    value: f32,
+}
+
+struct GrainResult {
+    position: f32,
+    l_value: f32,
+    r_value: f32,
+}
+
+impl Grain {
+    fn new() -> Self {
+        Grain {
+            position: 0.0,
+            state: GrainState::Stopped,
+            l_coef: 0.0,
+            r_coef: 0.0,
+            attack_slope: 0.0,
+            sustain_time: 0.0,
+            release_slope: 0.0,
+            value: 0.0,
+        }
+    }
+
+    #[inline]
+    fn trapezoid(&mut self) -> Option<f32> {
+        match self.state {
+            GrainState::Attack => {
+                self.value += self.attack_slope;
+                if self.value >= 1.0 {
+                    self.value = 1.0;
+                    self.state = GrainState::Sustain;
+                };
+                Some(self.value)
+            }
+            GrainState::Sustain => {
+                self.sustain_time -= 1.0;
+                if self.sustain_time <= 0.0 {
+                    self.state = GrainState::Release;
+                }
+                Some(1.0)
+            }
+            GrainState::Release => {
+                self.value += self.release_slope;
+                if self.value < 0.0 {
+                    self.value = 0.0;
+                    self.state = GrainState::Stopped;
+                };
+                Some(self.value)
+            }
+            GrainState::Stopped => None,
+        }
+    }
+
+    #[inline]
+    fn next(&mut self, step: f32, start: f32, end: f32) -> Option<GrainResult> {
+        self.trapezoid().map(|value| {
+            let position = self.position + step;
+            self.position = if position >= start && position <= end {
+                position
+            } else {
+                start + (position - start).rem_euclid(end - start)
+            };
+            GrainResult {
+                position: self.position,
+                l_value: value * self.l_coef,
+                r_value: value * self.r_coef,
+            }
+        })
+    }
+
+    fn recycle(&mut self, position: f32, pan_spread: f32, attack_slope: f32, sustain_time: f32, release_slope: f32) {
+        let (l_coef, r_coef) = get_pan(pan_spread * (2.0 * fastrand::f32() - 1.0));
+        self.position = position;
+        self.state = GrainState::Attack;
+        self.l_coef = l_coef;
+        self.r_coef = r_coef;
+        self.value = 0.0;
+        self.attack_slope = attack_slope;
+        self.sustain_time = sustain_time;
+        self.release_slope = release_slope;
+    }
+}
+
+// The scheduler and the pool of the baseline, only MAX_GRAINS is replaced by the pool size.
+struct Grains {
+    time_beetween_grains: f32,
+    current_time: f32,
+    grains: Vec<Grain>,
+}
+
+impl Grains {
+    fn grain_scheduler(&mut self, step: f32, scanner_location: f32, pan_spread: f32, attack_slope: f32, sustain_time: f32, release_slope: f32) {
+        self.current_time += step;
+        if self.current_time > self.time_beetween_grains {
+            // Create new grain if possible, if not we have to wait the next step...
+            if self.create_grain(scanner_location, pan_spread, attack_slope, sustain_time, release_slope) {
+                self.current_time = 0.0;
+            } else {
+                self.current_time = self.time_beetween_grains;
+            }
+        }
+    }
+
+    fn create_grain(&mut self, scanner_location: f32, pan_spread: f32, attack_slope: f32, sustain_time: f32, release_slope: f32) -> bool {
+        let opt_grain = self.grains
+            .iter_mut()
+            .filter(|g| g.state == GrainState::Stopped)
+            .nth(0);
+        if let Some(g) = opt_grain {
+            g.recycle(scanner_location, pan_spread, attack_slope, sustain_time, release_slope);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub struct PerSampleGranulator {
+    samples: SampleData,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    index: f32,
+    grains: Grains,
+    vec_result: Vec<GrainResult>,
+    pan_spread: f32,
+    attack_slope: f32,
+    sustain_duration: f32,
+    release_slope: f32,
+}
+
+impl PerSampleGranulator {
+    pub fn new(samples: SampleData, pool_size: usize, time_beetween_grains: f32) -> Self {
+        PerSampleGranulator {
+            samples,
+            output_left: Rc::new(RefCell::new(Buffer::new())),
+            output_right: Rc::new(RefCell::new(Buffer::new())),
+            index: 0.0,
+            grains: Grains {
+                time_beetween_grains,
+                current_time: 0.0,
+                grains: (0..pool_size).map(|_| Grain::new()).collect(),
+            },
+            vec_result: Vec::with_capacity(pool_size),
+            pan_spread: 0.0,
+            attack_slope: 0.1,
+            sustain_duration: 1_000.0,
+            release_slope: -0.1,
+        }
+    }
+
+    pub fn set_pan_spread(&mut self, pan_spread: f32) {
+        self.pan_spread = pan_spread;
+    }
+
+    pub fn set_grain_envelope(&mut self, attack_slope: f32, sustain_duration: f32, release_slope: f32) {
+        self.attack_slope = attack_slope;
+        self.sustain_duration = sustain_duration;
+        self.release_slope = release_slope;
+    }
+
+    pub fn active_grain_count(&self) -> usize {
+        self.grains.grains.iter().filter(|g| g.state != GrainState::Stopped).count()
+    }
+}
+
+impl Module for PerSampleGranulator {
+    fn process(&mut self) {
+        let output_left = self.output_left.clone();
+        let output_right = self.output_right.clone();
+        let mut wrapped_buf_left = output_left.try_borrow_mut().unwrap();
+        let mut wrapped_buf_right = output_right.try_borrow_mut().unwrap();
+        let buf_left = wrapped_buf_left.get_mut();
+        let buf_right = wrapped_buf_right.get_mut();
+        // -1 because of the interpolation.
+        let (start, end) = (0.0, self.samples.len() as f32 - 1.0);
+
+        for (b_l, b_r) in buf_left.iter_mut().zip(buf_right) {
+            self.grains.grain_scheduler(1.0, self.index, self.pan_spread, self.attack_slope, self.sustain_duration, self.release_slope);
+
+            let mut l_value = 0.0;
+            let mut r_value = 0.0;
+            let mut left = 0.0;
+            let mut right = 0.0;
+            self.vec_result.clear();
+            for g in self.grains.grains.iter_mut() {
+                if let Some(res) = g.next(1.0, start, end) {
+                    self.vec_result.push(res);
+                }
+            }
+            for r in self.vec_result.iter() {
+                l_value += r.l_value;
+                r_value += r.r_value;
+                let (sample_left, sample_right) = self.samples.interpolated_frame(r.position);
+                left += r.l_value * sample_left;
+                right += r.r_value * sample_right;
+            }
+            if l_value > 1.0 {
+                left /= l_value;
+            }
+            if r_value > 1.0 {
+                right /= r_value;
+            }
+            *b_l = left;
+            *b_r = right;
+            self.index = (self.index + 1.0) % end;
+        }
+    }
+}
+
+impl StereoGenerator for PerSampleGranulator {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
@@ -0,0 +1,159 @@
+use std::f32::consts::PI;
+
+use super::db_to_gain;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BiquadType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+    LowShelf,
+    HighShelf,
+    AllPass,
+}
+
+// RBJ audio EQ cookbook coefficients, normalized by a0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefs {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Default for BiquadCoefs {
+    fn default() -> Self {
+        BiquadCoefs {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        }
+    }
+}
+
+impl BiquadCoefs {
+    // gain_db is only used by Peak and shelves.
+    pub fn new(kind: BiquadType, sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let frequency = frequency.clamp(1.0, 0.49 * sample_rate);
+        let q = q.max(0.01);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a = db_to_gain(gain_db / 2.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadType::LowPass => {
+                let b = (1.0 - cos) / 2.0;
+                (b, 1.0 - cos, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadType::HighPass => {
+                let b = (1.0 + cos) / 2.0;
+                (b, -(1.0 + cos), b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadType::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::AllPass => (
+                1.0 - alpha,
+                -2.0 * cos,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadType::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BiquadType::LowShelf => {
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + s),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - s),
+                    (a + 1.0) + (a - 1.0) * cos + s,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - s,
+                )
+            }
+            BiquadType::HighShelf => {
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + s),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - s),
+                    (a + 1.0) - (a - 1.0) * cos + s,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - s,
+                )
+            }
+        };
+        BiquadCoefs {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    // |H(e^jw)| at the given frequency.
+    pub fn magnitude(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2.0 * PI * frequency / sample_rate;
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+}
+
+// Transposed direct form II
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    coefs: BiquadCoefs,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn new(coefs: BiquadCoefs) -> Self {
+        Biquad {
+            coefs,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn set_coefs(&mut self, coefs: BiquadCoefs) {
+        self.coefs = coefs;
+    }
+
+    pub fn coefs(&self) -> BiquadCoefs {
+        self.coefs
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefs;
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+}
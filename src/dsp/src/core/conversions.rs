@@ -0,0 +1,20 @@
+#[inline]
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+// Gain below -200 dB are reported as -200 dB
+#[inline]
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-10).log10()
+}
+
+// Exponential smoothing coefficient reaching ~63% of the target after `time_ms`.
+#[inline]
+pub fn time_constant_coef(time_ms: f32, sample_rate: f32) -> f32 {
+    if time_ms <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time_ms * 0.001 * sample_rate)).exp()
+    }
+}
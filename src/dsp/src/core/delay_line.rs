@@ -0,0 +1,52 @@
+// Circular delay line with fractional (linear interpolated) read.
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write_index: usize,
+}
+
+impl DelayLine {
+    pub fn new(max_delay: usize) -> Self {
+        DelayLine {
+            buffer: vec![0.0; max_delay.max(1) + 2],
+            write_index: 0,
+        }
+    }
+
+    pub fn resize(&mut self, max_delay: usize) {
+        self.buffer = vec![0.0; max_delay.max(1) + 2];
+        self.write_index = 0;
+    }
+
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len() - 2
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each(|v| *v = 0.0);
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: f32) {
+        self.write_index += 1;
+        if self.write_index >= self.buffer.len() {
+            self.write_index = 0;
+        }
+        self.buffer[self.write_index] = value;
+    }
+
+    // delay = 0 returns the last pushed value.
+    #[inline]
+    pub fn read(&self, delay: usize) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.min(self.max_delay());
+        self.buffer[(self.write_index + len - delay) % len]
+    }
+
+    #[inline]
+    pub fn read_interpolated(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(0.0, self.max_delay() as f32);
+        let idx = delay as usize;
+        let w = delay.fract();
+        (1.0 - w) * self.read(idx) + w * self.read(idx + 1)
+    }
+}
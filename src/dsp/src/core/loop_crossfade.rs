@@ -0,0 +1,80 @@
+use std::f32::consts::FRAC_PI_2;
+
+// How far set_start/set_end look for a zero crossing, in samples.
+pub const MAX_ZERO_CROSSING_DISTANCE: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrossfadeCurve {
+    Linear,
+    // Constant power, suited to uncorrelated material on both sides of the loop point.
+    EqualPower,
+}
+
+// Crossfade at a loop boundary.
+// Moving forward, the last `length` samples before the end are blended with the first `length`
+// samples after the start, then the playhead wraps to start + length (and symmetrically when
+// moving backward): the loop point is never heard as a jump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopCrossfade {
+    length: usize,
+    curve: CrossfadeCurve,
+}
+
+impl LoopCrossfade {
+    pub fn new(length: usize, curve: CrossfadeCurve) -> Self {
+        LoopCrossfade { length, curve }
+    }
+
+    // Samples, 0 disables the crossfade
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length;
+    }
+
+    pub fn set_curve(&mut self, curve: CrossfadeCurve) {
+        self.curve = curve;
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn curve(&self) -> CrossfadeCurve {
+        self.curve
+    }
+
+    // Crossfade length for a loop of loop_length samples: at most half the loop.
+    #[inline]
+    pub fn effective_length(&self, loop_length: f32) -> f32 {
+        (self.length as f32).min(loop_length / 2.0).max(0.0).floor()
+    }
+
+    // (fade out, fade in) gains at t in 0..1 through the crossfade.
+    #[inline]
+    pub fn gains(&self, t: f32) -> (f32, f32) {
+        let t = t.clamp(0.0, 1.0);
+        match self.curve {
+            CrossfadeCurve::Linear => (1.0 - t, t),
+            CrossfadeCurve::EqualPower => ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin()),
+        }
+    }
+}
+
+impl Default for LoopCrossfade {
+    fn default() -> Self {
+        LoopCrossfade::new(0, CrossfadeCurve::EqualPower)
+    }
+}
+
+// Index of the zero crossing closest to `index` (within max_distance), `index` if there is none.
+pub fn find_zero_crossing(samples: &[f32], index: usize, max_distance: usize) -> usize {
+    let is_crossing = |i: usize| i > 0 && i < samples.len() && (samples[i - 1] <= 0.0) != (samples[i] <= 0.0);
+    for distance in 0..=max_distance {
+        if is_crossing(index + distance) {
+            return index + distance;
+        }
+        if distance <= index && is_crossing(index - distance) {
+            return index - distance;
+        }
+    }
+    index
+}
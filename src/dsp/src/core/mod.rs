@@ -6,3 +6,27 @@ pub use module::*;
 
 mod osc_receiver;
 pub use osc_receiver::*;
+
+mod delay_line;
+pub use delay_line::*;
+
+mod wav_loader;
+pub use wav_loader::*;
+
+mod conversions;
+pub use conversions::*;
+
+mod oversampling;
+pub use oversampling::*;
+
+mod biquad;
+pub use biquad::*;
+
+mod window;
+pub use window::*;
+
+mod loop_crossfade;
+pub use loop_crossfade::*;
+
+mod sample_data;
+pub use sample_data::*;
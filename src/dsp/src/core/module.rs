@@ -33,3 +33,12 @@ impl StereoGenerator for dyn MonoGenerator {
         return self.get_output();
     }
 }
+
+pub trait StereoProcessor: StereoGenerator {
+    fn set_left_input(&mut self, input: SharedBuffer);
+    fn set_right_input(&mut self, input: SharedBuffer);
+}
+
+pub trait MonoProcessor: MonoGenerator {
+    fn set_input(&mut self, input: SharedBuffer);
+}
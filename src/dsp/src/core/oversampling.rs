@@ -0,0 +1,385 @@
+use std::f32::consts::PI;
+
+use super::{
+    Buffer, Module, MonoGenerator, MonoProcessor, SharedBuffer, StereoGenerator, StereoProcessor,
+};
+
+// Taps of the anti imaging / anti aliasing filters per polyphase branch.
+const TAPS_PER_PHASE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OversamplingFactor {
+    X1,
+    X2,
+    X4,
+    X8,
+}
+
+impl OversamplingFactor {
+    pub fn value(&self) -> usize {
+        match self {
+            OversamplingFactor::X1 => 1,
+            OversamplingFactor::X2 => 2,
+            OversamplingFactor::X4 => 4,
+            OversamplingFactor::X8 => 8,
+        }
+    }
+}
+
+// Blackman windowed sinc low pass, cutoff at 80% of the original nyquist.
+// No oversampling: dirac, the filters are transparent.
+fn lowpass_taps(factor: usize) -> Vec<f32> {
+    let len = factor * TAPS_PER_PHASE;
+    if factor == 1 {
+        let mut taps = vec![0.0; len];
+        taps[0] = 1.0;
+        return taps;
+    }
+    let cutoff = 0.4 / factor as f32;
+    let center = (len - 1) as f32 / 2.0;
+    let mut taps: Vec<f32> = (0..len)
+        .map(|i| {
+            let t = i as f32 - center;
+            let sinc = if t == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * t).sin() / (PI * t)
+            };
+            let w = 2.0 * PI * i as f32 / (len - 1) as f32;
+            let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+            sinc * window
+        })
+        .collect();
+    let sum: f32 = taps.iter().sum();
+    taps.iter_mut().for_each(|t| *t /= sum);
+    taps
+}
+
+// Zero stuffing + low pass, computed with one polyphase branch per output phase.
+pub struct Upsampler {
+    factor: usize,
+    phases: Vec<Vec<f32>>,
+    history: Vec<f32>,
+    index: usize,
+}
+
+impl Upsampler {
+    pub fn new(factor: OversamplingFactor) -> Self {
+        let factor = factor.value();
+        let taps = lowpass_taps(factor);
+        let phases = (0..factor)
+            .map(|p| {
+                (0..TAPS_PER_PHASE)
+                    .map(|m| factor as f32 * taps[m * factor + p])
+                    .collect()
+            })
+            .collect();
+        Upsampler {
+            factor,
+            phases,
+            history: vec![0.0; TAPS_PER_PHASE],
+            index: 0,
+        }
+    }
+
+    // Writes `factor` samples into output.
+    #[inline]
+    pub fn process(&mut self, x: f32, output: &mut [f32]) {
+        self.index = (self.index + 1) % TAPS_PER_PHASE;
+        self.history[self.index] = x;
+        for (out, phase) in output[..self.factor].iter_mut().zip(&self.phases) {
+            let mut acc = 0.0;
+            for (m, coef) in phase.iter().enumerate() {
+                acc += coef * self.history[(self.index + TAPS_PER_PHASE - m) % TAPS_PER_PHASE];
+            }
+            *out = acc;
+        }
+    }
+}
+
+// Low pass + decimation, only the kept samples are computed.
+pub struct Downsampler {
+    factor: usize,
+    taps: Vec<f32>,
+    history: Vec<f32>,
+    index: usize,
+}
+
+impl Downsampler {
+    pub fn new(factor: OversamplingFactor) -> Self {
+        let factor = factor.value();
+        let taps = lowpass_taps(factor);
+        Downsampler {
+            factor,
+            history: vec![0.0; taps.len()],
+            taps,
+            index: 0,
+        }
+    }
+
+    // Consumes `factor` samples.
+    #[inline]
+    pub fn process(&mut self, input: &[f32]) -> f32 {
+        let len = self.taps.len();
+        for x in &input[..self.factor] {
+            self.index = (self.index + 1) % len;
+            self.history[self.index] = *x;
+        }
+        let mut acc = 0.0;
+        for (k, coef) in self.taps.iter().enumerate() {
+            acc += coef * self.history[(self.index + len - k) % len];
+        }
+        acc
+    }
+}
+
+// Runs a stereo module at `factor` times the sample rate (see MonoOversampler for mono modules).
+pub struct Oversampler<M: StereoProcessor> {
+    module: M,
+    factor: OversamplingFactor,
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    inner_left: SharedBuffer,
+    inner_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    upsamplers: [Upsampler; 2],
+    downsamplers: [Downsampler; 2],
+    upsampled: [Vec<f32>; 2],
+    processed: [Vec<f32>; 2],
+}
+
+impl<M: StereoProcessor> Oversampler<M> {
+    pub fn new(mut module: M, factor: OversamplingFactor) -> Self {
+        let inner_left = SharedBuffer::default();
+        let inner_right = SharedBuffer::default();
+        module.set_left_input(inner_left.clone());
+        module.set_right_input(inner_right.clone());
+        let mut oversampler = Oversampler {
+            module,
+            factor,
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            inner_left,
+            inner_right,
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            upsamplers: [Upsampler::new(factor), Upsampler::new(factor)],
+            downsamplers: [Downsampler::new(factor), Downsampler::new(factor)],
+            upsampled: [vec![], vec![]],
+            processed: [vec![], vec![]],
+        };
+        oversampler.set_factor(factor);
+        oversampler
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    pub fn set_factor(&mut self, factor: OversamplingFactor) {
+        self.factor = factor;
+        self.upsamplers = [Upsampler::new(factor), Upsampler::new(factor)];
+        self.downsamplers = [Downsampler::new(factor), Downsampler::new(factor)];
+        let len = factor.value() * Buffer::size();
+        self.upsampled = [vec![0.0; len], vec![0.0; len]];
+        self.processed = [vec![0.0; len], vec![0.0; len]];
+        self.module
+            .set_sample_rate(self.sample_rate * factor.value() as f32);
+    }
+
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    pub fn module_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+}
+
+impl<M: StereoProcessor> Module for Oversampler<M> {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.module
+            .set_sample_rate(sample_rate * self.factor.value() as f32);
+    }
+
+    fn process(&mut self) {
+        let factor = self.factor.value();
+        let size = Buffer::size();
+        let inputs = [self.input_left.clone(), self.input_right.clone()];
+        let inners = [self.inner_left.clone(), self.inner_right.clone()];
+        let outputs = [self.output_left.clone(), self.output_right.clone()];
+
+        let upsampling = inputs.iter().zip(self.upsamplers.iter_mut()).zip(self.upsampled.iter_mut());
+        for ((input, upsampler), upsampled) in upsampling {
+            let input = input.try_borrow().unwrap();
+            for (x, out) in input.get().iter().zip(upsampled.chunks_mut(factor)) {
+                upsampler.process(*x, out);
+            }
+        }
+
+        let module_outputs = [self.module.get_left_output(), self.module.get_right_output()];
+        for chunk in 0..factor {
+            let range = chunk * size..(chunk + 1) * size;
+            for (inner, upsampled) in inners.iter().zip(self.upsampled.iter()) {
+                let mut inner = inner.try_borrow_mut().unwrap();
+                inner.get_mut().copy_from_slice(&upsampled[range.clone()]);
+            }
+            self.module.process();
+            for (module_output, processed) in module_outputs.iter().zip(self.processed.iter_mut()) {
+                let module_output = module_output.try_borrow().unwrap();
+                processed[range.clone()].copy_from_slice(module_output.get());
+            }
+        }
+
+        let downsampling = outputs.iter().zip(self.downsamplers.iter_mut()).zip(self.processed.iter());
+        for ((output, downsampler), processed) in downsampling {
+            let mut output = output.try_borrow_mut().unwrap();
+            for (out, samples) in output.get_mut().iter_mut().zip(processed.chunks(factor)) {
+                *out = downsampler.process(samples);
+            }
+        }
+    }
+}
+
+impl<M: StereoProcessor> StereoGenerator for Oversampler<M> {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl<M: StereoProcessor> StereoProcessor for Oversampler<M> {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Oversampler::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Oversampler::set_right_input(self, input)
+    }
+}
+
+// Runs a mono module at `factor` times the sample rate.
+pub struct MonoOversampler<M: MonoProcessor> {
+    module: M,
+    factor: OversamplingFactor,
+    sample_rate: f32,
+    input: SharedBuffer,
+    inner: SharedBuffer,
+    output: SharedBuffer,
+    upsampler: Upsampler,
+    downsampler: Downsampler,
+    upsampled: Vec<f32>,
+    processed: Vec<f32>,
+}
+
+impl<M: MonoProcessor> MonoOversampler<M> {
+    pub fn new(mut module: M, factor: OversamplingFactor) -> Self {
+        let inner = SharedBuffer::default();
+        module.set_input(inner.clone());
+        let mut oversampler = MonoOversampler {
+            module,
+            factor,
+            sample_rate: 44_100.0,
+            input: SharedBuffer::default(),
+            inner,
+            output: SharedBuffer::default(),
+            upsampler: Upsampler::new(factor),
+            downsampler: Downsampler::new(factor),
+            upsampled: vec![],
+            processed: vec![],
+        };
+        oversampler.set_factor(factor);
+        oversampler
+    }
+
+    pub fn set_input(&mut self, input: SharedBuffer) {
+        self.input = input;
+    }
+
+    pub fn set_factor(&mut self, factor: OversamplingFactor) {
+        self.factor = factor;
+        self.upsampler = Upsampler::new(factor);
+        self.downsampler = Downsampler::new(factor);
+        let len = factor.value() * Buffer::size();
+        self.upsampled = vec![0.0; len];
+        self.processed = vec![0.0; len];
+        self.module
+            .set_sample_rate(self.sample_rate * factor.value() as f32);
+    }
+
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    pub fn module_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+}
+
+impl<M: MonoProcessor> Module for MonoOversampler<M> {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.module
+            .set_sample_rate(sample_rate * self.factor.value() as f32);
+    }
+
+    fn process(&mut self) {
+        let factor = self.factor.value();
+        let size = Buffer::size();
+
+        {
+            let input = self.input.try_borrow().unwrap();
+            for (x, out) in input.get().iter().zip(self.upsampled.chunks_mut(factor)) {
+                self.upsampler.process(*x, out);
+            }
+        }
+
+        let module_output = self.module.get_output();
+        for chunk in 0..factor {
+            let range = chunk * size..(chunk + 1) * size;
+            {
+                let mut inner = self.inner.try_borrow_mut().unwrap();
+                inner.get_mut().copy_from_slice(&self.upsampled[range.clone()]);
+            }
+            self.module.process();
+            let module_output = module_output.try_borrow().unwrap();
+            self.processed[range].copy_from_slice(module_output.get());
+        }
+
+        let mut output = self.output.try_borrow_mut().unwrap();
+        for (out, samples) in output.get_mut().iter_mut().zip(self.processed.chunks(factor)) {
+            *out = self.downsampler.process(samples);
+        }
+    }
+}
+
+impl<M: MonoProcessor> MonoGenerator for MonoOversampler<M> {
+    fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+}
+
+impl<M: MonoProcessor> MonoProcessor for MonoOversampler<M> {
+    fn set_input(&mut self, input: SharedBuffer) {
+        MonoOversampler::set_input(self, input)
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+// Multichannel sample storage, one Vec per channel (all the same length).
+// Played as stereo: mono is sent to both sides, with more than two channels the even channels
+// are mixed to the left and the odd ones to the right.
+// The channels are shared: a clone does not copy the samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleData {
+    channels: Arc<[Vec<f32>]>,
+    len: usize,
+}
+
+impl SampleData {
+    // Channels are truncated to the shortest one.
+    pub fn new(channels: Vec<Vec<f32>>) -> Self {
+        let len = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+        let mut channels = channels;
+        channels.iter_mut().for_each(|c| c.truncate(len));
+        if channels.is_empty() {
+            channels.push(vec![]);
+        }
+        SampleData {
+            channels: channels.into(),
+            len,
+        }
+    }
+
+    pub fn from_mono(samples: Vec<f32>) -> Self {
+        SampleData::new(vec![samples])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        &self.channels[channel]
+    }
+
+    // Left and right channels: the mono channel twice, or the first two channels
+    // (see into_stereo when there are more).
+    pub fn stereo_channels(&self) -> (&[f32], &[f32]) {
+        let right = self.channels.len().min(2) - 1;
+        (&self.channels[0], &self.channels[right])
+    }
+
+    // Mixed down to the two channels played (see frame), unchanged for mono and stereo.
+    pub fn into_stereo(self) -> SampleData {
+        if self.channels.len() <= 2 {
+            return self;
+        }
+        let (left, right) = (0..self.len).map(|i| self.frame(i)).unzip();
+        SampleData::new(vec![left, right])
+    }
+
+    // Average of all the channels.
+    pub fn to_mono(&self) -> Vec<f32> {
+        let gain = 1.0 / self.channels.len() as f32;
+        (0..self.len)
+            .map(|i| gain * self.channels.iter().map(|c| c[i]).sum::<f32>())
+            .collect()
+    }
+
+    // (left, right) at index, silence out of bounds.
+    #[inline]
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        if index >= self.len {
+            return (0.0, 0.0);
+        }
+        match self.channels.len() {
+            1 => {
+                let v = self.channels[0][index];
+                (v, v)
+            }
+            2 => (self.channels[0][index], self.channels[1][index]),
+            count => {
+                let (mut left, mut right) = (0.0, 0.0);
+                for (c, channel) in self.channels.iter().enumerate() {
+                    if c % 2 == 0 {
+                        left += channel[index];
+                    } else {
+                        right += channel[index];
+                    }
+                }
+                let left_count = count.div_ceil(2) as f32;
+                let right_count = (count / 2) as f32;
+                (left / left_count, right / right_count)
+            }
+        }
+    }
+
+    // Linear interpolation between index and index + 1.
+    #[inline]
+    pub fn interpolated_frame(&self, position: f32) -> (f32, f32) {
+        let i = position as usize;
+        let w = position.fract();
+        let (l0, r0) = self.frame(i);
+        let (l1, r1) = if i + 1 < self.len { self.frame(i + 1) } else { (l0, r0) };
+        ((1.0 - w) * l0 + w * l1, (1.0 - w) * r0 + w * r1)
+    }
+}
+
+impl Default for SampleData {
+    fn default() -> Self {
+        SampleData::new(vec![])
+    }
+}
+
+impl From<Vec<f32>> for SampleData {
+    fn from(samples: Vec<f32>) -> Self {
+        SampleData::from_mono(samples)
+    }
+}
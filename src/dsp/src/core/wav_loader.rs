@@ -0,0 +1,70 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WavLoaderError {
+    #[error("Unable to open the wav file: `{0}`")]
+    FailedToOpenFile(String),
+    #[error("Unable to decode the wav file: `{0}`")]
+    FailedToDecodeFile(String),
+}
+
+// Load a wav file, convert data -> -1<f32<1 and resample every channel
+// to the given sample rate. Returns one Vec per channel.
+pub fn load_wav_file(file_name: &str, sample_rate: f32) -> Result<Vec<Vec<f32>>, WavLoaderError> {
+    let mut reader = hound::WavReader::open(file_name)
+        .map_err(|_e| WavLoaderError::FailedToOpenFile(file_name.to_owned()))?;
+    let spec = reader.spec();
+    println!("Channels: {}", spec.channels);
+    println!("Sample rate: {}", spec.sample_rate);
+    println!("Bits_per_sample: {}", spec.bits_per_sample);
+    let original_sample_rate = spec.sample_rate as f32;
+    let channels = spec.channels as usize;
+
+    let max_value = 2.0_f32.powi(spec.bits_per_sample as i32);
+    let interleaved: Vec<f32> = reader
+        .samples::<i32>()
+        .map(|e| e.map(|v| (v as f32) / max_value))
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_e| WavLoaderError::FailedToDecodeFile(file_name.to_owned()))?;
+
+    let ratio = sample_rate / original_sample_rate;
+    let result = (0..channels)
+        .map(|channel| {
+            let original_data: Vec<f32> = interleaved
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect();
+            resample(&original_data, ratio)
+        })
+        .collect();
+    Ok(result)
+}
+
+pub fn resample(original_data: &[f32], ratio: f32) -> Vec<f32> {
+    let original_wav_len = original_data.len() as f32;
+    let new_size = (ratio * original_wav_len).round() as usize;
+    let mut samples = Vec::with_capacity(new_size);
+    if ratio < 1.0 {
+        // Down sampling
+        for target_idx in 0..new_size {
+            let source_idx = (target_idx as f32 / ratio).round() as usize;
+            if source_idx >= original_data.len() {
+                break;
+            }
+            samples.push(original_data[source_idx]);
+        }
+    } else {
+        // Upper sampling
+        for target_idx in 0..new_size {
+            let source_idx = (target_idx as f32 / ratio).trunc() as usize;
+            let coef = (target_idx as f32 / ratio).fract();
+            if source_idx + 1 >= original_data.len() {
+                break;
+            }
+            samples.push((1.0 - coef) * original_data[source_idx] + original_data[source_idx + 1] * coef);
+        }
+    }
+    samples
+}
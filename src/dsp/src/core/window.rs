@@ -0,0 +1,24 @@
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowType {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+// Periodic window of the given size (suited to overlap-add and spectral analysis).
+pub fn window(kind: WindowType, size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|i| {
+            let x = 2.0 * PI * i as f32 / size as f32;
+            match kind {
+                WindowType::Rectangular => 1.0,
+                WindowType::Hann => 0.5 - 0.5 * x.cos(),
+                WindowType::Hamming => 0.54 - 0.46 * x.cos(),
+                WindowType::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,3 @@
+mod spectrum;
+
+pub use spectrum::*;
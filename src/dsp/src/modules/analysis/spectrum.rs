@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::core::{gain_to_db, window, Module, SharedBuffer, WindowType};
+
+pub const MIN_FFT_SIZE: usize = 256;
+pub const MAX_FFT_SIZE: usize = 16384;
+
+// Averaged magnitude spectrum of the (mono summed) input, binned on a log frequency scale.
+pub struct SpectrumAnalyzer {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    fft_size: usize,
+    window_type: WindowType,
+    window: Vec<f32>,
+    window_gain: f32,
+    overlap: f32,
+    hop: usize,
+    averaging: f32,
+    band_count: usize,
+    min_frequency: f32,
+    max_frequency: f32,
+    fft: Arc<dyn Fft<f32>>,
+    fft_buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    history: Vec<f32>,
+    history_index: usize,
+    hop_counter: usize,
+    magnitudes: Vec<f32>,
+    frame: Vec<f32>,
+    new_frame: bool,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new() -> Self {
+        let fft_size = 2048;
+        let mut planner = FftPlanner::new();
+        let mut analyzer = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            fft_size,
+            window_type: WindowType::Hann,
+            window: vec![],
+            window_gain: 1.0,
+            overlap: 0.5,
+            hop: fft_size / 2,
+            averaging: 0.7,
+            band_count: 64,
+            min_frequency: 20.0,
+            max_frequency: 20_000.0,
+            fft: planner.plan_fft_forward(fft_size),
+            fft_buffer: vec![],
+            scratch: vec![],
+            history: vec![],
+            history_index: 0,
+            hop_counter: 0,
+            magnitudes: vec![],
+            frame: vec![],
+            new_frame: false,
+        };
+        analyzer.reset();
+        analyzer
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // Rounded to the next power of two, 256..16384
+    pub fn set_fft_size(&mut self, fft_size: usize) {
+        self.fft_size = fft_size.clamp(MIN_FFT_SIZE, MAX_FFT_SIZE).next_power_of_two();
+        self.reset();
+    }
+
+    pub fn set_window(&mut self, window_type: WindowType) {
+        self.window_type = window_type;
+        self.reset();
+    }
+
+    // 0..0.875 of the fft size
+    pub fn set_overlap(&mut self, overlap: f32) {
+        self.overlap = overlap.clamp(0.0, 0.875);
+        self.reset();
+    }
+
+    // Exponential averaging of the successive spectra, 0 (none) ..0.99
+    pub fn set_averaging(&mut self, averaging: f32) {
+        self.averaging = averaging.clamp(0.0, 0.99);
+    }
+
+    // Number of log spaced bands between min and max frequency.
+    pub fn set_bands(&mut self, band_count: usize, min_frequency: f32, max_frequency: f32) {
+        if band_count > 0 && min_frequency > 0.0 && min_frequency < max_frequency {
+            self.band_count = band_count;
+            self.min_frequency = min_frequency;
+            self.max_frequency = max_frequency;
+            self.frame = vec![gain_to_db(0.0); band_count];
+        }
+    }
+
+    // Latest spectrum in dB (one value per band), if a new one was computed since the last call.
+    pub fn take_frame(&mut self) -> Option<Vec<f32>> {
+        if self.new_frame {
+            self.new_frame = false;
+            Some(self.frame.clone())
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        let mut planner = FftPlanner::new();
+        self.fft = planner.plan_fft_forward(self.fft_size);
+        self.fft_buffer = vec![Complex::default(); self.fft_size];
+        self.scratch = vec![Complex::default(); self.fft.get_inplace_scratch_len()];
+        self.window = window(self.window_type, self.fft_size);
+        self.window_gain = self.window.iter().sum::<f32>() / 2.0;
+        self.hop = ((self.fft_size as f32 * (1.0 - self.overlap)) as usize).max(1);
+        self.history = vec![0.0; self.fft_size];
+        self.history_index = 0;
+        self.hop_counter = 0;
+        self.magnitudes = vec![0.0; self.fft_size / 2 + 1];
+        self.frame = vec![gain_to_db(0.0); self.band_count];
+    }
+
+    fn analyze(&mut self) {
+        // Oldest sample first
+        let size = self.fft_size;
+        for (i, c) in self.fft_buffer.iter_mut().enumerate() {
+            let sample = self.history[(self.history_index + i) % size];
+            *c = Complex::new(sample * self.window[i], 0.0);
+        }
+        self.fft.process_with_scratch(&mut self.fft_buffer, &mut self.scratch);
+
+        let averaging = self.averaging;
+        for (m, c) in self.magnitudes.iter_mut().zip(&self.fft_buffer) {
+            let magnitude = c.norm() / self.window_gain;
+            *m = averaging * *m + (1.0 - averaging) * magnitude;
+        }
+
+        let bin_width = self.sample_rate / size as f32;
+        let ratio = self.max_frequency / self.min_frequency;
+        let last_bin = self.magnitudes.len() - 1;
+        for (band, value) in self.frame.iter_mut().enumerate() {
+            let low = self.min_frequency * ratio.powf(band as f32 / self.band_count as f32);
+            let high = self.min_frequency * ratio.powf((band + 1) as f32 / self.band_count as f32);
+            let low_bin = ((low / bin_width).ceil() as usize).min(last_bin);
+            let high_bin = ((high / bin_width).floor() as usize).min(last_bin);
+            let magnitude = if low_bin <= high_bin {
+                self.magnitudes[low_bin..=high_bin]
+                    .iter()
+                    .fold(0.0_f32, |acc, m| acc.max(*m))
+            } else {
+                // Band narrower than a bin: interpolate at the band center.
+                let position = ((low * high).sqrt() / bin_width).min(last_bin as f32);
+                let idx = (position as usize).min(last_bin - 1);
+                let w = position - idx as f32;
+                (1.0 - w) * self.magnitudes[idx] + w * self.magnitudes[idx + 1]
+            };
+            *value = gain_to_db(magnitude);
+        }
+        self.new_frame = true;
+    }
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SpectrumAnalyzer {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.max_frequency = self.max_frequency.min(sample_rate / 2.0);
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        for (l, r) in input_left.get().iter().zip(input_right.get()) {
+            self.history[self.history_index] = (l + r) / 2.0;
+            self.history_index = (self.history_index + 1) % self.fft_size;
+            self.hop_counter += 1;
+            if self.hop_counter >= self.hop {
+                self.hop_counter = 0;
+                self.analyze();
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::core::{Module, MonoGenerator, MonoProcessor, SharedBuffer, StereoGenerator};
+
+// Gate output: 1 while the input is above the threshold (with hysteresis), 0 otherwise.
+// Trigger output: pulse of `pulse_ms` on each rising edge of the gate.
+pub struct Comparator {
+    sample_rate: f32,
+    input: SharedBuffer,
+    gate_output: SharedBuffer,
+    trigger_output: SharedBuffer,
+    threshold: f32,
+    hysteresis: f32,
+    pulse_ms: f32,
+    gate: bool,
+    pulse_remaining: usize,
+    triggered: bool,
+}
+
+impl Comparator {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            input: SharedBuffer::default(),
+            gate_output: SharedBuffer::default(),
+            trigger_output: SharedBuffer::default(),
+            threshold: 0.5,
+            hysteresis: 0.05,
+            pulse_ms: 1.0,
+            gate: false,
+            pulse_remaining: 0,
+            triggered: false,
+        }
+    }
+
+    pub fn set_input(&mut self, input: SharedBuffer) {
+        self.input = input;
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    // The gate closes `hysteresis` below the threshold.
+    pub fn set_hysteresis(&mut self, hysteresis: f32) {
+        self.hysteresis = hysteresis.max(0.0);
+    }
+
+    pub fn set_pulse_length(&mut self, pulse_ms: f32) {
+        self.pulse_ms = pulse_ms.max(0.0);
+    }
+
+    pub fn gate(&self) -> bool {
+        self.gate
+    }
+
+    // True if a rising edge occurred during the last processed chunk.
+    pub fn triggered(&self) -> bool {
+        self.triggered
+    }
+
+    pub fn get_gate_output(&self) -> SharedBuffer {
+        self.gate_output.clone()
+    }
+
+    pub fn get_trigger_output(&self) -> SharedBuffer {
+        self.trigger_output.clone()
+    }
+}
+
+impl Default for Comparator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Comparator {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn process(&mut self) {
+        let input = self.input.try_borrow().unwrap();
+        let mut gate_output = self.gate_output.try_borrow_mut().unwrap();
+        let mut trigger_output = self.trigger_output.try_borrow_mut().unwrap();
+
+        let pulse_length = ((self.pulse_ms * 0.001 * self.sample_rate) as usize).max(1);
+        self.triggered = false;
+        let outputs = gate_output.get_mut().iter_mut().zip(trigger_output.get_mut());
+        for ((gate, trigger), x) in outputs.zip(input.get()) {
+            let open = if self.gate {
+                *x > self.threshold - self.hysteresis
+            } else {
+                *x >= self.threshold
+            };
+            if open && !self.gate {
+                self.pulse_remaining = pulse_length;
+                self.triggered = true;
+            }
+            self.gate = open;
+            *gate = if open { 1.0 } else { 0.0 };
+            *trigger = if self.pulse_remaining > 0 {
+                self.pulse_remaining -= 1;
+                1.0
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+impl MonoGenerator for Comparator {
+    fn get_output(&self) -> SharedBuffer {
+        Comparator::get_gate_output(self)
+    }
+}
+
+impl StereoGenerator for Comparator {
+    fn get_left_output(&self) -> SharedBuffer {
+        Comparator::get_gate_output(self)
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        Comparator::get_trigger_output(self)
+    }
+}
+
+impl MonoProcessor for Comparator {
+    fn set_input(&mut self, input: SharedBuffer) {
+        Comparator::set_input(self, input)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::core::{Module, MonoGenerator, MonoProcessor, SharedBuffer, StereoGenerator};
+use crate::modules::dynamics::{DetectionMode, EnvelopeDetector};
+
+// Turns an audio signal into a modulation signal: offset + gain * envelope
+pub struct EnvelopeFollower {
+    input: SharedBuffer,
+    output: SharedBuffer,
+    detector: EnvelopeDetector,
+    gain: f32,
+    offset: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new() -> Self {
+        Self {
+            input: SharedBuffer::default(),
+            output: SharedBuffer::default(),
+            detector: EnvelopeDetector::new(DetectionMode::Peak, 5.0, 100.0),
+            gain: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    pub fn set_input(&mut self, input: SharedBuffer) {
+        self.input = input;
+    }
+
+    pub fn set_mode(&mut self, mode: DetectionMode) {
+        self.detector.set_mode(mode);
+    }
+
+    pub fn set_attack(&mut self, attack_ms: f32) {
+        self.detector.set_attack(attack_ms);
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.detector.set_release(release_ms);
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset;
+    }
+
+    // Last output value, to drive parameters once per chunk.
+    pub fn value(&self) -> f32 {
+        self.offset + self.gain * self.detector.value()
+    }
+
+    pub fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+}
+
+impl Default for EnvelopeFollower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for EnvelopeFollower {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.detector.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self) {
+        let input = self.input.try_borrow().unwrap();
+        let mut output = self.output.try_borrow_mut().unwrap();
+        for (o, x) in output.get_mut().iter_mut().zip(input.get()) {
+            *o = self.offset + self.gain * self.detector.process(*x);
+        }
+    }
+}
+
+impl MonoGenerator for EnvelopeFollower {
+    fn get_output(&self) -> SharedBuffer {
+        EnvelopeFollower::get_output(self)
+    }
+}
+
+impl StereoGenerator for EnvelopeFollower {
+    fn get_left_output(&self) -> SharedBuffer {
+        EnvelopeFollower::get_output(self)
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        EnvelopeFollower::get_output(self)
+    }
+}
+
+impl MonoProcessor for EnvelopeFollower {
+    fn set_input(&mut self, input: SharedBuffer) {
+        EnvelopeFollower::set_input(self, input)
+    }
+}
@@ -0,0 +1,9 @@
+mod comparator;
+mod envelope_follower;
+mod sample_and_hold;
+mod slew_limiter;
+
+pub use comparator::*;
+pub use envelope_follower::*;
+pub use sample_and_hold::*;
+pub use slew_limiter::*;
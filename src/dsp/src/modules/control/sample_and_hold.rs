@@ -0,0 +1,83 @@
+use crate::core::{Module, MonoGenerator, SharedBuffer, StereoGenerator};
+
+// Samples the input on each rising edge of the trigger input (crossing the threshold).
+pub struct SampleAndHold {
+    input: SharedBuffer,
+    trigger: SharedBuffer,
+    output: SharedBuffer,
+    threshold: f32,
+    trigger_high: bool,
+    held: f32,
+}
+
+impl SampleAndHold {
+    pub fn new() -> Self {
+        Self {
+            input: SharedBuffer::default(),
+            trigger: SharedBuffer::default(),
+            output: SharedBuffer::default(),
+            threshold: 0.5,
+            trigger_high: false,
+            held: 0.0,
+        }
+    }
+
+    pub fn set_input(&mut self, input: SharedBuffer) {
+        self.input = input;
+    }
+
+    pub fn set_trigger_input(&mut self, trigger: SharedBuffer) {
+        self.trigger = trigger;
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn value(&self) -> f32 {
+        self.held
+    }
+
+    pub fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+}
+
+impl Default for SampleAndHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SampleAndHold {
+    fn process(&mut self) {
+        let input = self.input.try_borrow().unwrap();
+        let trigger = self.trigger.try_borrow().unwrap();
+        let mut output = self.output.try_borrow_mut().unwrap();
+        let samples = input.get().iter().zip(trigger.get());
+        for (o, (x, t)) in output.get_mut().iter_mut().zip(samples) {
+            let high = *t >= self.threshold;
+            if high && !self.trigger_high {
+                self.held = *x;
+            }
+            self.trigger_high = high;
+            *o = self.held;
+        }
+    }
+}
+
+impl MonoGenerator for SampleAndHold {
+    fn get_output(&self) -> SharedBuffer {
+        SampleAndHold::get_output(self)
+    }
+}
+
+impl StereoGenerator for SampleAndHold {
+    fn get_left_output(&self) -> SharedBuffer {
+        SampleAndHold::get_output(self)
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        SampleAndHold::get_output(self)
+    }
+}
@@ -0,0 +1,111 @@
+use crate::core::{Module, MonoGenerator, MonoProcessor, SharedBuffer, StereoGenerator};
+
+// Limits the speed of the input changes: rise/fall times are the time for a change of 1.0
+pub struct SlewLimiter {
+    sample_rate: f32,
+    input: SharedBuffer,
+    output: SharedBuffer,
+    rise_ms: f32,
+    fall_ms: f32,
+    max_rise: f32,
+    max_fall: f32,
+    value: f32,
+}
+
+impl SlewLimiter {
+    pub fn new() -> Self {
+        let mut slew = Self {
+            sample_rate: 44_100.0,
+            input: SharedBuffer::default(),
+            output: SharedBuffer::default(),
+            rise_ms: 10.0,
+            fall_ms: 10.0,
+            max_rise: 0.0,
+            max_fall: 0.0,
+            value: 0.0,
+        };
+        slew.update_steps();
+        slew
+    }
+
+    pub fn set_input(&mut self, input: SharedBuffer) {
+        self.input = input;
+    }
+
+    pub fn set_rise(&mut self, rise_ms: f32) {
+        self.rise_ms = rise_ms.max(0.0);
+        self.update_steps();
+    }
+
+    pub fn set_fall(&mut self, fall_ms: f32) {
+        self.fall_ms = fall_ms.max(0.0);
+        self.update_steps();
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+
+    fn update_steps(&mut self) {
+        let sample_rate = self.sample_rate;
+        let step = |time_ms: f32| {
+            let samples = time_ms * 0.001 * sample_rate;
+            if samples < 1.0 {
+                f32::INFINITY
+            } else {
+                1.0 / samples
+            }
+        };
+        self.max_rise = step(self.rise_ms);
+        self.max_fall = step(self.fall_ms);
+    }
+}
+
+impl Default for SlewLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SlewLimiter {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_steps();
+    }
+
+    fn process(&mut self) {
+        let input = self.input.try_borrow().unwrap();
+        let mut output = self.output.try_borrow_mut().unwrap();
+        for (o, x) in output.get_mut().iter_mut().zip(input.get()) {
+            let delta = (x - self.value).clamp(-self.max_fall, self.max_rise);
+            self.value += delta;
+            *o = self.value;
+        }
+    }
+}
+
+impl MonoGenerator for SlewLimiter {
+    fn get_output(&self) -> SharedBuffer {
+        SlewLimiter::get_output(self)
+    }
+}
+
+impl StereoGenerator for SlewLimiter {
+    fn get_left_output(&self) -> SharedBuffer {
+        SlewLimiter::get_output(self)
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        SlewLimiter::get_output(self)
+    }
+}
+
+impl MonoProcessor for SlewLimiter {
+    fn set_input(&mut self, input: SharedBuffer) {
+        SlewLimiter::set_input(self, input)
+    }
+}
@@ -0,0 +1,161 @@
+use crate::core::{db_to_gain, gain_to_db, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+use super::{DetectionMode, EnvelopeDetector, GainReductionMeter};
+
+// Stereo linked feed forward compressor with soft knee.
+pub struct Compressor {
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    sidechain_left: Option<SharedBuffer>,
+    sidechain_right: Option<SharedBuffer>,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    detector: EnvelopeDetector,
+    threshold_db: f32,
+    ratio: f32,
+    knee_db: f32,
+    makeup_gain: f32,
+    meter: GainReductionMeter,
+}
+
+impl Compressor {
+    pub fn new() -> Self {
+        Self {
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            sidechain_left: None,
+            sidechain_right: None,
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            detector: EnvelopeDetector::new(DetectionMode::Peak, 10.0, 100.0),
+            threshold_db: -18.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+            makeup_gain: 1.0,
+            meter: GainReductionMeter::new(),
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // The detector listens to the sidechain instead of the input when set.
+    pub fn set_sidechain_inputs(&mut self, left: Option<SharedBuffer>, right: Option<SharedBuffer>) {
+        self.sidechain_left = left;
+        self.sidechain_right = right;
+    }
+
+    pub fn set_threshold(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    pub fn set_knee(&mut self, knee_db: f32) {
+        self.knee_db = knee_db.max(0.0);
+    }
+
+    pub fn set_attack(&mut self, attack_ms: f32) {
+        self.detector.set_attack(attack_ms);
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.detector.set_release(release_ms);
+    }
+
+    pub fn set_detection_mode(&mut self, mode: DetectionMode) {
+        self.detector.set_mode(mode);
+    }
+
+    pub fn set_makeup_gain(&mut self, makeup_db: f32) {
+        self.makeup_gain = db_to_gain(makeup_db);
+    }
+
+    pub fn gain_reduction_meter(&self) -> GainReductionMeter {
+        self.meter.clone()
+    }
+
+    // Static curve: gain (in dB, <= 0) to apply for a given level.
+    #[inline]
+    fn gain_computer(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let slope = 1.0 / self.ratio - 1.0;
+        if 2.0 * over < -self.knee_db {
+            0.0
+        } else if self.knee_db > 0.0 && 2.0 * over.abs() <= self.knee_db {
+            let x = over + self.knee_db / 2.0;
+            slope * x * x / (2.0 * self.knee_db)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Compressor {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.detector.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let sidechain_left = self.sidechain_left.as_ref().map(|s| s.try_borrow().unwrap());
+        let sidechain_right = self.sidechain_right.as_ref().map(|s| s.try_borrow().unwrap());
+        let key_left = sidechain_left.as_deref().unwrap_or(&*input_left);
+        let key_right = sidechain_right.as_deref().unwrap_or(&*input_right);
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+
+        let mut max_reduction: f32 = 0.0;
+        let keys = key_left.get().iter().zip(key_right.get());
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for (((k_l, k_r), (in_l, in_r)), (out_l, out_r)) in keys.zip(inputs).zip(outputs) {
+            let level = match self.detector.mode() {
+                DetectionMode::Peak => k_l.abs().max(k_r.abs()),
+                DetectionMode::Rms => ((k_l * k_l + k_r * k_r) / 2.0).sqrt(),
+            };
+            let envelope = self.detector.process(level);
+            let reduction_db = self.gain_computer(gain_to_db(envelope));
+            max_reduction = max_reduction.max(-reduction_db);
+
+            let gain = self.makeup_gain * db_to_gain(reduction_db);
+            *out_l = gain * in_l;
+            *out_r = gain * in_r;
+        }
+        self.meter.set(max_reduction);
+    }
+}
+
+impl StereoGenerator for Compressor {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Compressor {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Compressor::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Compressor::set_right_input(self, input)
+    }
+}
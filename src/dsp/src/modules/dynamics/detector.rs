@@ -0,0 +1,85 @@
+use crate::core::time_constant_coef;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetectionMode {
+    Peak,
+    Rms,
+}
+
+// Attack/release envelope of a signal.
+pub struct EnvelopeDetector {
+    sample_rate: f32,
+    mode: DetectionMode,
+    attack_ms: f32,
+    release_ms: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    envelope: f32,
+}
+
+impl EnvelopeDetector {
+    pub fn new(mode: DetectionMode, attack_ms: f32, release_ms: f32) -> Self {
+        let mut detector = EnvelopeDetector {
+            sample_rate: 44_100.0,
+            mode,
+            attack_ms,
+            release_ms,
+            attack_coef: 0.0,
+            release_coef: 0.0,
+            envelope: 0.0,
+        };
+        detector.update_coefs();
+        detector
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coefs();
+    }
+
+    pub fn set_mode(&mut self, mode: DetectionMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> DetectionMode {
+        self.mode
+    }
+
+    pub fn set_attack(&mut self, attack_ms: f32) {
+        self.attack_ms = attack_ms.max(0.0);
+        self.update_coefs();
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.max(0.0);
+        self.update_coefs();
+    }
+
+    fn update_coefs(&mut self) {
+        self.attack_coef = time_constant_coef(self.attack_ms, self.sample_rate);
+        self.release_coef = time_constant_coef(self.release_ms, self.sample_rate);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let input = match self.mode {
+            DetectionMode::Peak => x.abs(),
+            DetectionMode::Rms => x * x,
+        };
+        let coef = if input > self.envelope {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.envelope = input + coef * (self.envelope - input);
+        self.value()
+    }
+
+    #[inline]
+    pub fn value(&self) -> f32 {
+        match self.mode {
+            DetectionMode::Peak => self.envelope,
+            DetectionMode::Rms => self.envelope.sqrt(),
+        }
+    }
+}
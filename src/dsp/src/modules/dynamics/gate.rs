@@ -0,0 +1,214 @@
+use crate::core::{db_to_gain, gain_to_db, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+use super::{DetectionMode, EnvelopeDetector, GainReductionMeter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GateState {
+    Open,
+    Hold,
+    Closed,
+}
+
+// Stereo linked noise gate with hysteresis, hold and range.
+pub struct NoiseGate {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    sidechain_left: Option<SharedBuffer>,
+    sidechain_right: Option<SharedBuffer>,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    detector: EnvelopeDetector,
+    state: GateState,
+    open_threshold: f32,
+    close_threshold: f32,
+    threshold_db: f32,
+    hysteresis_db: f32,
+    attack_ms: f32,
+    hold_ms: f32,
+    release_ms: f32,
+    floor: f32,
+    hold_counter: f32,
+    gain: f32,
+    meter: GainReductionMeter,
+}
+
+impl NoiseGate {
+    pub fn new() -> Self {
+        let mut gate = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            sidechain_left: None,
+            sidechain_right: None,
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            detector: EnvelopeDetector::new(DetectionMode::Peak, 0.0, 5.0),
+            state: GateState::Closed,
+            open_threshold: 0.0,
+            close_threshold: 0.0,
+            threshold_db: -50.0,
+            hysteresis_db: 4.0,
+            attack_ms: 1.0,
+            hold_ms: 50.0,
+            release_ms: 100.0,
+            floor: 0.0,
+            hold_counter: 0.0,
+            gain: 0.0,
+            meter: GainReductionMeter::new(),
+        };
+        gate.update_thresholds();
+        gate
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // The gate opens on the sidechain level instead of the input level when set.
+    pub fn set_sidechain_inputs(&mut self, left: Option<SharedBuffer>, right: Option<SharedBuffer>) {
+        self.sidechain_left = left;
+        self.sidechain_right = right;
+    }
+
+    pub fn set_threshold(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+        self.update_thresholds();
+    }
+
+    // The gate closes `hysteresis_db` below the threshold.
+    pub fn set_hysteresis(&mut self, hysteresis_db: f32) {
+        self.hysteresis_db = hysteresis_db.max(0.0);
+        self.update_thresholds();
+    }
+
+    pub fn set_attack(&mut self, attack_ms: f32) {
+        self.attack_ms = attack_ms.max(0.0);
+    }
+
+    pub fn set_hold(&mut self, hold_ms: f32) {
+        self.hold_ms = hold_ms.max(0.0);
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.max(0.0);
+    }
+
+    // Attenuation when closed, -inf..0 dB
+    pub fn set_range(&mut self, range_db: f32) {
+        self.floor = if range_db <= -120.0 {
+            0.0
+        } else {
+            db_to_gain(range_db.min(0.0))
+        };
+    }
+
+    pub fn gain_reduction_meter(&self) -> GainReductionMeter {
+        self.meter.clone()
+    }
+
+    fn update_thresholds(&mut self) {
+        self.open_threshold = db_to_gain(self.threshold_db);
+        self.close_threshold = db_to_gain(self.threshold_db - self.hysteresis_db);
+    }
+
+    // Linear ramp increment covering the full gain range in `time_ms`.
+    #[inline]
+    fn ramp(&self, time_ms: f32) -> f32 {
+        let samples = time_ms * 0.001 * self.sample_rate;
+        if samples < 1.0 {
+            1.0
+        } else {
+            (1.0 - self.floor) / samples
+        }
+    }
+
+    #[inline]
+    fn next_gain(&mut self, level: f32) -> f32 {
+        let envelope = self.detector.process(level);
+        self.state = match self.state {
+            _ if envelope >= self.open_threshold => GateState::Open,
+            GateState::Open if envelope < self.close_threshold => {
+                self.hold_counter = self.hold_ms * 0.001 * self.sample_rate;
+                GateState::Hold
+            }
+            GateState::Hold => {
+                self.hold_counter -= 1.0;
+                if self.hold_counter <= 0.0 {
+                    GateState::Closed
+                } else {
+                    GateState::Hold
+                }
+            }
+            state => state,
+        };
+        self.gain = match self.state {
+            GateState::Open | GateState::Hold => (self.gain + self.ramp(self.attack_ms)).min(1.0),
+            GateState::Closed => (self.gain - self.ramp(self.release_ms)).max(self.floor),
+        };
+        self.gain
+    }
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for NoiseGate {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.detector.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let sidechain_left = self.sidechain_left.clone().unwrap_or_else(|| input_left.clone());
+        let sidechain_right = self.sidechain_right.clone().unwrap_or_else(|| input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let key_left = sidechain_left.try_borrow().unwrap();
+        let key_right = sidechain_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let mut min_gain: f32 = 1.0;
+        let keys = key_left.get().iter().zip(key_right.get());
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for (((k_l, k_r), (in_l, in_r)), (out_l, out_r)) in keys.zip(inputs).zip(outputs) {
+            let gain = self.next_gain(k_l.abs().max(k_r.abs()));
+            min_gain = min_gain.min(gain);
+            *out_l = gain * in_l;
+            *out_r = gain * in_r;
+        }
+        self.meter.set(-gain_to_db(min_gain));
+    }
+}
+
+impl StereoGenerator for NoiseGate {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for NoiseGate {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        NoiseGate::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        NoiseGate::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+
+use crate::core::{
+    db_to_gain, gain_to_db, time_constant_coef, DelayLine, Module, SharedBuffer, StereoGenerator,
+    StereoProcessor,
+};
+
+use super::GainReductionMeter;
+
+const MAX_LOOKAHEAD_MS: f32 = 20.0;
+
+// Lookahead brickwall limiter: the gain is the minimum of the needed gains over the
+// lookahead window, smoothed by a moving average of the same length, so it has reached
+// its final value when the delayed peak is played.
+pub struct Limiter {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    sidechain_left: Option<SharedBuffer>,
+    sidechain_right: Option<SharedBuffer>,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    ceiling: f32,
+    lookahead_ms: f32,
+    lookahead: usize,
+    release_ms: f32,
+    release_coef: f32,
+    delay_left: DelayLine,
+    delay_right: DelayLine,
+    // Sliding minimum (index, gain), increasing gains from front to back.
+    minimums: VecDeque<(usize, f32)>,
+    sample_index: usize,
+    released_gain: f32,
+    average: Vec<f32>,
+    average_index: usize,
+    average_sum: f64,
+    meter: GainReductionMeter,
+}
+
+impl Limiter {
+    pub fn new() -> Self {
+        let mut limiter = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            sidechain_left: None,
+            sidechain_right: None,
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            ceiling: db_to_gain(-0.3),
+            lookahead_ms: 5.0,
+            lookahead: 1,
+            release_ms: 50.0,
+            release_coef: 0.0,
+            delay_left: DelayLine::new(1),
+            delay_right: DelayLine::new(1),
+            minimums: VecDeque::new(),
+            sample_index: 0,
+            released_gain: 1.0,
+            average: vec![],
+            average_index: 0,
+            average_sum: 0.0,
+            meter: GainReductionMeter::new(),
+        };
+        limiter.reset();
+        limiter
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // The gain is computed from the sidechain instead of the input when set.
+    pub fn set_sidechain_inputs(&mut self, left: Option<SharedBuffer>, right: Option<SharedBuffer>) {
+        self.sidechain_left = left;
+        self.sidechain_right = right;
+    }
+
+    pub fn set_ceiling(&mut self, ceiling_db: f32) {
+        self.ceiling = db_to_gain(ceiling_db.min(0.0));
+    }
+
+    pub fn set_lookahead(&mut self, lookahead_ms: f32) {
+        self.lookahead_ms = lookahead_ms.clamp(0.0, MAX_LOOKAHEAD_MS);
+        self.reset();
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.max(0.0);
+        self.release_coef = time_constant_coef(self.release_ms, self.sample_rate);
+    }
+
+    pub fn set_gain_reduction_meter(&mut self, meter: GainReductionMeter) {
+        self.meter = meter;
+    }
+
+    pub fn gain_reduction_meter(&self) -> GainReductionMeter {
+        self.meter.clone()
+    }
+
+    // Delay (in samples) added to the signal.
+    pub fn latency(&self) -> usize {
+        self.lookahead - 1
+    }
+
+    fn reset(&mut self) {
+        self.lookahead = ((self.lookahead_ms * self.sample_rate / 1000.0) as usize).max(1);
+        self.release_coef = time_constant_coef(self.release_ms, self.sample_rate);
+        self.delay_left = DelayLine::new(self.lookahead);
+        self.delay_right = DelayLine::new(self.lookahead);
+        self.minimums = VecDeque::with_capacity(self.lookahead + 1);
+        self.sample_index = 0;
+        self.released_gain = 1.0;
+        self.average = vec![1.0; self.lookahead];
+        self.average_index = 0;
+        self.average_sum = self.lookahead as f64;
+    }
+
+    #[inline]
+    fn next_gain(&mut self, peak: f32) -> f32 {
+        let target = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        // Minimum of the targets over the lookahead window.
+        while let Some((_, gain)) = self.minimums.back() {
+            if *gain >= target {
+                self.minimums.pop_back();
+            } else {
+                break;
+            }
+        }
+        self.minimums.push_back((self.sample_index, target));
+        while let Some((index, _)) = self.minimums.front() {
+            if index + self.lookahead <= self.sample_index {
+                self.minimums.pop_front();
+            } else {
+                break;
+            }
+        }
+        let minimum = self.minimums.front().map(|(_, g)| *g).unwrap_or(1.0);
+        self.sample_index += 1;
+
+        // Instant attack, exponential release, never above the minimum.
+        self.released_gain =
+            minimum.min(self.released_gain + (1.0 - self.released_gain) * (1.0 - self.release_coef));
+
+        // Moving average over the lookahead window.
+        self.average_sum += (self.released_gain - self.average[self.average_index]) as f64;
+        self.average[self.average_index] = self.released_gain;
+        self.average_index = (self.average_index + 1) % self.lookahead;
+        (self.average_sum / self.lookahead as f64) as f32
+    }
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Limiter {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reset();
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let sidechain_left = self.sidechain_left.clone().unwrap_or_else(|| input_left.clone());
+        let sidechain_right = self.sidechain_right.clone().unwrap_or_else(|| input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let key_left = sidechain_left.try_borrow().unwrap();
+        let key_right = sidechain_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let delay = self.lookahead - 1;
+        let ceiling = self.ceiling;
+        let mut min_gain: f32 = 1.0;
+        let keys = key_left.get().iter().zip(key_right.get());
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for (((k_l, k_r), (in_l, in_r)), (out_l, out_r)) in keys.zip(inputs).zip(outputs) {
+            let gain = self.next_gain(k_l.abs().max(k_r.abs()));
+            min_gain = min_gain.min(gain);
+
+            self.delay_left.push(*in_l);
+            self.delay_right.push(*in_r);
+            // Clamp to protect against rounding errors of the moving average.
+            *out_l = (gain * self.delay_left.read(delay)).clamp(-ceiling, ceiling);
+            *out_r = (gain * self.delay_right.read(delay)).clamp(-ceiling, ceiling);
+        }
+        self.meter.set(-gain_to_db(min_gain));
+    }
+}
+
+impl StereoGenerator for Limiter {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Limiter {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Limiter::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Limiter::set_right_input(self, input)
+    }
+}
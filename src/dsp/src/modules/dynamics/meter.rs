@@ -0,0 +1,25 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+// Gain reduction (in positive dB) of a dynamics module, shared with other threads (GUI).
+#[derive(Clone, Default)]
+pub struct GainReductionMeter {
+    value: Arc<AtomicU32>,
+}
+
+impl GainReductionMeter {
+    pub fn new() -> Self {
+        GainReductionMeter::default()
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.value.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set(&self, gain_reduction_db: f32) {
+        self.value
+            .store(gain_reduction_db.to_bits(), Ordering::Relaxed);
+    }
+}
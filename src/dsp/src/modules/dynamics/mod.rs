@@ -0,0 +1,11 @@
+mod compressor;
+mod detector;
+mod gate;
+mod limiter;
+mod meter;
+
+pub use compressor::*;
+pub use detector::*;
+pub use gate::*;
+pub use limiter::*;
+pub use meter::*;
@@ -0,0 +1,187 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::core::{
+    Biquad, BiquadCoefs, BiquadType, Module, SharedBuffer, StereoGenerator, StereoProcessor,
+};
+
+// Bit depth reduction and sample and hold decimation.
+pub struct Bitcrusher {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    bits: f32,
+    target_rate: f32,
+    anti_alias: bool,
+    // Two cascaded biquads: 24dB/oct
+    filters: [[Biquad; 2]; 2],
+    dither: bool,
+    jitter: f32,
+    phase: f32,
+    next_hold: f32,
+    held: [f32; 2],
+    mix: f32,
+}
+
+impl Bitcrusher {
+    pub fn new() -> Self {
+        let mut crusher = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            bits: 8.0,
+            target_rate: 11_025.0,
+            anti_alias: false,
+            filters: [[Biquad::default(); 2]; 2],
+            dither: false,
+            jitter: 0.0,
+            phase: 1.0,
+            next_hold: 1.0,
+            held: [0.0; 2],
+            mix: 1.0,
+        };
+        crusher.update_filters();
+        crusher
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // 1..24, fractional values allowed.
+    pub fn set_bits(&mut self, bits: f32) {
+        self.bits = bits.clamp(1.0, 24.0);
+    }
+
+    // Hz, up to the sample rate (no decimation).
+    pub fn set_target_rate(&mut self, target_rate: f32) {
+        self.target_rate = target_rate.clamp(20.0, self.sample_rate);
+        self.update_filters();
+    }
+
+    // Low pass the input at the nyquist of the target rate before holding.
+    pub fn set_anti_alias(&mut self, anti_alias: bool) {
+        self.anti_alias = anti_alias;
+    }
+
+    // TPDF dither before quantization.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+    }
+
+    // Random variation of the hold period, 0..1
+    pub fn set_jitter(&mut self, jitter: f32) {
+        self.jitter = jitter.clamp(0.0, 1.0);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update_filters(&mut self) {
+        let coefs = BiquadCoefs::new(
+            BiquadType::LowPass,
+            self.sample_rate,
+            0.45 * self.target_rate,
+            FRAC_1_SQRT_2,
+            0.0,
+        );
+        self.filters
+            .iter_mut()
+            .flatten()
+            .for_each(|f| f.set_coefs(coefs));
+    }
+
+    #[inline]
+    fn quantize(&self, x: f32) -> f32 {
+        let step = 2.0 / 2.0_f32.powf(self.bits);
+        let x = if self.dither {
+            x + step * (fastrand::f32() - fastrand::f32())
+        } else {
+            x
+        };
+        ((x / step).round() * step).clamp(-1.0, 1.0)
+    }
+
+    #[inline]
+    fn process_frame(&mut self, x: [f32; 2]) -> [f32; 2] {
+        let mut filtered = x;
+        if self.anti_alias {
+            for (v, filters) in filtered.iter_mut().zip(self.filters.iter_mut()) {
+                *v = filters.iter_mut().fold(*v, |acc, f| f.process(acc));
+            }
+        }
+
+        // Sample and hold, both sides share the same clock.
+        self.phase += self.target_rate / self.sample_rate;
+        if self.phase >= self.next_hold {
+            self.phase -= self.next_hold;
+            self.next_hold = 1.0 + self.jitter * (fastrand::f32() - 0.5);
+            self.held = [self.quantize(filtered[0]), self.quantize(filtered[1])];
+        }
+
+        [
+            (1.0 - self.mix) * x[0] + self.mix * self.held[0],
+            (1.0 - self.mix) * x[1] + self.mix * self.held[1],
+        ]
+    }
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Bitcrusher {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.target_rate = self.target_rate.min(sample_rate);
+        self.update_filters();
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            let [l, r] = self.process_frame([*in_l, *in_r]);
+            *out_l = l;
+            *out_r = r;
+        }
+    }
+}
+
+impl StereoGenerator for Bitcrusher {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Bitcrusher {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Bitcrusher::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Bitcrusher::set_right_input(self, input)
+    }
+}
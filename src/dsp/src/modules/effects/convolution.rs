@@ -0,0 +1,364 @@
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::core::{Buffer, DelayLine, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+const MAX_PRE_DELAY_MS: f32 = 500.0;
+const TRIM_FADE_LENGTH: usize = 64;
+// Partition sizes of the non uniform scheme. The first one must be the chunk size.
+const NON_UNIFORM_PARTITIONS: [usize; 4] = [32, 256, 2048, 16384];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConvolutionPartitioning {
+    // Every partition has the size of a chunk: constant cpu load, expensive for long IRs.
+    Uniform,
+    // Small partitions for the head of the IR, bigger ones for the tail.
+    NonUniform,
+}
+
+// Uniformly partitioned overlap-save convolution of one segment of the IR.
+// A stage with a block bigger than a chunk produces its output one block late,
+// so it must convolve a segment starting at least `block_size` samples in the IR.
+struct ConvolutionStage {
+    block_size: usize,
+    delayed: bool,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    partitions: Vec<Vec<Complex<f32>>>,
+    spectra: Vec<Vec<Complex<f32>>>,
+    spectra_index: usize,
+    input: Vec<f32>,
+    input_fill: usize,
+    output: Vec<f32>,
+    output_index: usize,
+    fft_buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl ConvolutionStage {
+    fn new(planner: &mut FftPlanner<f32>, segment: &[f32], block_size: usize) -> Self {
+        let fft_size = 2 * block_size;
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+        let mut scratch = vec![Complex::default(); scratch_len];
+
+        let partitions: Vec<Vec<Complex<f32>>> = segment
+            .chunks(block_size)
+            .map(|chunk| {
+                let mut spectrum = vec![Complex::default(); fft_size];
+                for (s, v) in spectrum.iter_mut().zip(chunk) {
+                    s.re = *v;
+                }
+                fft.process_with_scratch(&mut spectrum, &mut scratch);
+                spectrum
+            })
+            .collect();
+        let spectra = vec![vec![Complex::default(); fft_size]; partitions.len()];
+
+        ConvolutionStage {
+            block_size,
+            delayed: block_size > Buffer::size(),
+            fft,
+            ifft,
+            partitions,
+            spectra,
+            spectra_index: 0,
+            input: vec![0.0; fft_size],
+            input_fill: 0,
+            output: vec![0.0; block_size],
+            output_index: 0,
+            fft_buffer: vec![Complex::default(); fft_size],
+            scratch,
+        }
+    }
+
+    fn compute(&mut self) {
+        let fft_size = 2 * self.block_size;
+        for (c, v) in self.fft_buffer.iter_mut().zip(&self.input) {
+            *c = Complex::new(*v, 0.0);
+        }
+        self.fft.process_with_scratch(&mut self.fft_buffer, &mut self.scratch);
+        self.spectra[self.spectra_index].copy_from_slice(&self.fft_buffer);
+
+        // Multiply-accumulate the frequency domain delay line with the IR partitions.
+        let count = self.partitions.len();
+        self.fft_buffer.iter_mut().for_each(|c| *c = Complex::default());
+        for (p, partition) in self.partitions.iter().enumerate() {
+            let spectrum = &self.spectra[(self.spectra_index + count - p) % count];
+            for ((acc, s), h) in self.fft_buffer.iter_mut().zip(spectrum).zip(partition) {
+                *acc += s * h;
+            }
+        }
+        self.spectra_index = (self.spectra_index + 1) % count;
+
+        self.ifft.process_with_scratch(&mut self.fft_buffer, &mut self.scratch);
+        let norm = 1.0 / fft_size as f32;
+        for (o, c) in self.output.iter_mut().zip(&self.fft_buffer[self.block_size..]) {
+            *o = c.re * norm;
+        }
+        self.input.copy_within(self.block_size.., 0);
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        let n = input.len();
+        let offset = self.block_size + self.input_fill;
+        self.input[offset..offset + n].copy_from_slice(input);
+        self.input_fill += n;
+
+        if self.delayed {
+            let previous = &self.output[self.output_index..self.output_index + n];
+            output.iter_mut().zip(previous).for_each(|(o, v)| *o += v);
+            self.output_index += n;
+        }
+        if self.input_fill >= self.block_size {
+            self.compute();
+            self.input_fill = 0;
+            self.output_index = 0;
+        }
+        if !self.delayed {
+            output.iter_mut().zip(&self.output).for_each(|(o, v)| *o += v);
+        }
+    }
+}
+
+struct Convolver {
+    stages: Vec<ConvolutionStage>,
+}
+
+impl Convolver {
+    fn new(ir: &[f32], partitioning: ConvolutionPartitioning) -> Self {
+        let mut planner = FftPlanner::new();
+        let chunk = Buffer::size();
+        let stages = match partitioning {
+            ConvolutionPartitioning::Uniform => {
+                if ir.is_empty() {
+                    vec![]
+                } else {
+                    vec![ConvolutionStage::new(&mut planner, ir, chunk)]
+                }
+            }
+            ConvolutionPartitioning::NonUniform => {
+                let sizes = NON_UNIFORM_PARTITIONS;
+                let mut stages = vec![];
+                for (i, block_size) in sizes.iter().enumerate() {
+                    let start = if i == 0 { 0 } else { *block_size };
+                    let end = if i + 1 < sizes.len() { sizes[i + 1] } else { ir.len() };
+                    if start >= ir.len() {
+                        break;
+                    }
+                    let segment = &ir[start..end.min(ir.len())];
+                    stages.push(ConvolutionStage::new(&mut planner, segment, *block_size));
+                }
+                stages
+            }
+        };
+        Convolver { stages }
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        output.iter_mut().for_each(|o| *o = 0.0);
+        for stage in self.stages.iter_mut() {
+            stage.process(input, output);
+        }
+    }
+}
+
+pub struct ConvolutionReverb {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    impulse_response: Vec<Vec<f32>>,
+    partitioning: ConvolutionPartitioning,
+    trim_start: usize, // Included
+    trim_end: usize,   // Excluded
+    convolver_left: Convolver,
+    convolver_right: Convolver,
+    pre_delay_ms: f32,
+    pre_delay_left: DelayLine,
+    pre_delay_right: DelayLine,
+    mix: f32,
+    wet: Buffer,
+}
+
+impl ConvolutionReverb {
+    pub fn new() -> Self {
+        let sample_rate = 44_100_f32;
+        let max_pre_delay = (MAX_PRE_DELAY_MS * sample_rate / 1000.0) as usize;
+        let partitioning = ConvolutionPartitioning::NonUniform;
+        // Dirac: the reverb is transparent until an IR is loaded.
+        let impulse_response = vec![vec![1.0]];
+        Self {
+            sample_rate,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            convolver_left: Convolver::new(&impulse_response[0], partitioning),
+            convolver_right: Convolver::new(&impulse_response[0], partitioning),
+            impulse_response,
+            partitioning,
+            trim_start: 0,
+            trim_end: 1,
+            pre_delay_ms: 0.0,
+            pre_delay_left: DelayLine::new(max_pre_delay),
+            pre_delay_right: DelayLine::new(max_pre_delay),
+            mix: 0.3,
+            wet: Buffer::new(),
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // One Vec per channel, already resampled (see `load_wav_file`).
+    // A mono IR is used for both sides, a stereo IR convolves left and right independently.
+    pub fn load_impulse_response(&mut self, impulse_response: Vec<Vec<f32>>) {
+        let impulse_response: Vec<Vec<f32>> =
+            impulse_response.into_iter().filter(|c| !c.is_empty()).take(2).collect();
+        if impulse_response.is_empty() {
+            return;
+        }
+        self.trim_start = 0;
+        self.trim_end = impulse_response[0].len();
+        self.impulse_response = impulse_response;
+        self.rebuild();
+    }
+
+    pub fn set_trim(&mut self, start: usize, end: usize) {
+        let len = self.impulse_response[0].len();
+        let end = end.min(len);
+        if start < end {
+            self.trim_start = start;
+            self.trim_end = end;
+            self.rebuild();
+        }
+    }
+
+    pub fn set_partitioning(&mut self, partitioning: ConvolutionPartitioning) {
+        if self.partitioning != partitioning {
+            self.partitioning = partitioning;
+            self.rebuild();
+        }
+    }
+
+    pub fn set_pre_delay(&mut self, pre_delay_ms: f32) {
+        self.pre_delay_ms = pre_delay_ms.clamp(0.0, MAX_PRE_DELAY_MS);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    // Trim and fade out the cut tail.
+    fn prepare_channel(&self, channel: &[f32]) -> Vec<f32> {
+        let end = self.trim_end.min(channel.len());
+        let start = self.trim_start.min(end);
+        let mut ir: Vec<f32> = channel[start..end].to_vec();
+        if end < channel.len() {
+            let fade = TRIM_FADE_LENGTH.min(ir.len());
+            let len = ir.len();
+            for (i, v) in ir[len - fade..].iter_mut().enumerate() {
+                *v *= 1.0 - (i + 1) as f32 / fade as f32;
+            }
+        }
+        ir
+    }
+
+    fn rebuild(&mut self) {
+        let mut left = self.prepare_channel(&self.impulse_response[0]);
+        let mut right = match self.impulse_response.get(1) {
+            Some(channel) => self.prepare_channel(channel),
+            None => left.clone(),
+        };
+        // One factor for both sides keeps the balance of the IR, the louder one gets a unit energy.
+        let energy = |ir: &[f32]| ir.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let energy = energy(&left).max(energy(&right));
+        if energy > 0.0 {
+            left.iter_mut().chain(right.iter_mut()).for_each(|v| *v /= energy);
+        }
+        self.convolver_left = Convolver::new(&left, self.partitioning);
+        self.convolver_right = Convolver::new(&right, self.partitioning);
+    }
+}
+
+impl Default for ConvolutionReverb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for ConvolutionReverb {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let max_pre_delay = (MAX_PRE_DELAY_MS * sample_rate / 1000.0) as usize;
+        self.pre_delay_left.resize(max_pre_delay);
+        self.pre_delay_right.resize(max_pre_delay);
+    }
+
+    fn process(&mut self) {
+        let delay = (self.pre_delay_ms * self.sample_rate / 1000.0) as usize;
+        let dry_gain = 1.0 - self.mix;
+        let wet_gain = self.mix;
+        let sides = [
+            (
+                &self.input_left,
+                &self.output_left,
+                &mut self.pre_delay_left,
+                &mut self.convolver_left,
+            ),
+            (
+                &self.input_right,
+                &self.output_right,
+                &mut self.pre_delay_right,
+                &mut self.convolver_right,
+            ),
+        ];
+        for (input, output, pre_delay, convolver) in sides {
+            let input = input.try_borrow().unwrap();
+            let mut output = output.try_borrow_mut().unwrap();
+
+            let mut delayed = Buffer::new();
+            for (d, i) in delayed.get_mut().iter_mut().zip(input.get()) {
+                pre_delay.push(*i);
+                *d = pre_delay.read(delay);
+            }
+            convolver.process(delayed.get(), self.wet.get_mut());
+
+            for ((o, i), w) in output.get_mut().iter_mut().zip(input.get()).zip(self.wet.get()) {
+                *o = dry_gain * i + wet_gain * w;
+            }
+        }
+    }
+}
+
+impl StereoGenerator for ConvolutionReverb {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for ConvolutionReverb {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        ConvolutionReverb::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        ConvolutionReverb::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,255 @@
+use std::f32::consts::PI;
+
+use crate::core::{
+    db_to_gain, Module, OversamplingFactor, Oversampler, SharedBuffer, StereoGenerator,
+    StereoProcessor,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistortionCurve {
+    Tanh,
+    HardClip,
+    Foldback,
+    AsymmetricTube,
+    Quantize,
+}
+
+// Memoryless transfer curve followed by a DC blocker. Aliases: run it inside an `Oversampler`.
+pub struct Waveshaper {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    curve: DistortionCurve,
+    drive: f32,
+    output_gain: f32,
+    bias: f32,
+    bits: f32,
+    mix: f32,
+    dc_coef: f32,
+    dc_state: [(f32, f32); 2],
+}
+
+impl Waveshaper {
+    pub fn new() -> Self {
+        let mut shaper = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            curve: DistortionCurve::Tanh,
+            drive: 1.0,
+            output_gain: 1.0,
+            bias: 0.2,
+            bits: 8.0,
+            mix: 1.0,
+            dc_coef: 0.0,
+            dc_state: [(0.0, 0.0); 2],
+        };
+        shaper.update_dc_coef();
+        shaper
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    pub fn set_curve(&mut self, curve: DistortionCurve) {
+        self.curve = curve;
+    }
+
+    pub fn set_drive(&mut self, drive_db: f32) {
+        self.drive = db_to_gain(drive_db.max(0.0));
+    }
+
+    pub fn set_output_level(&mut self, level_db: f32) {
+        self.output_gain = db_to_gain(level_db);
+    }
+
+    // Asymmetry of the tube curve, 0..1
+    pub fn set_asymmetry(&mut self, asymmetry: f32) {
+        self.bias = asymmetry.clamp(0.0, 1.0);
+    }
+
+    // Bit depth of the quantize curve, fractional values allowed, 1..24
+    pub fn set_bits(&mut self, bits: f32) {
+        self.bits = bits.clamp(1.0, 24.0);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update_dc_coef(&mut self) {
+        // One pole high pass at 10Hz
+        self.dc_coef = 1.0 - 2.0 * PI * 10.0 / self.sample_rate;
+    }
+
+    #[inline]
+    fn shape(&self, x: f32) -> f32 {
+        match self.curve {
+            DistortionCurve::Tanh => x.tanh(),
+            DistortionCurve::HardClip => x.clamp(-1.0, 1.0),
+            DistortionCurve::Foldback => {
+                // Triangle folding: reflects the signal on -1 and 1.
+                let t = (x - 1.0) / 4.0;
+                4.0 * (t - t.floor() - 0.5).abs() - 1.0
+            }
+            DistortionCurve::AsymmetricTube => (x + self.bias).tanh() - self.bias.tanh(),
+            DistortionCurve::Quantize => {
+                let levels = 2.0_f32.powf(self.bits - 1.0);
+                ((x * levels).round() / levels).clamp(-1.0, 1.0)
+            }
+        }
+    }
+
+    #[inline]
+    fn process_sample(&mut self, side: usize, x: f32) -> f32 {
+        let shaped = self.shape(self.drive * x);
+        let (x1, y1) = self.dc_state[side];
+        let y = shaped - x1 + self.dc_coef * y1;
+        self.dc_state[side] = (shaped, y);
+        (1.0 - self.mix) * x + self.mix * self.output_gain * y
+    }
+}
+
+impl Default for Waveshaper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Waveshaper {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_dc_coef();
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            *out_l = self.process_sample(0, *in_l);
+            *out_r = self.process_sample(1, *in_r);
+        }
+    }
+}
+
+impl StereoGenerator for Waveshaper {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Waveshaper {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Waveshaper::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Waveshaper::set_right_input(self, input)
+    }
+}
+
+// Waveshaper running oversampled.
+pub struct Distortion {
+    oversampler: Oversampler<Waveshaper>,
+}
+
+impl Distortion {
+    pub fn new() -> Self {
+        Self {
+            oversampler: Oversampler::new(Waveshaper::new(), OversamplingFactor::X4),
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.oversampler.set_left_input(input);
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.oversampler.set_right_input(input);
+    }
+
+    pub fn set_oversampling(&mut self, factor: OversamplingFactor) {
+        self.oversampler.set_factor(factor);
+    }
+
+    pub fn set_curve(&mut self, curve: DistortionCurve) {
+        self.oversampler.module_mut().set_curve(curve);
+    }
+
+    pub fn set_drive(&mut self, drive_db: f32) {
+        self.oversampler.module_mut().set_drive(drive_db);
+    }
+
+    pub fn set_output_level(&mut self, level_db: f32) {
+        self.oversampler.module_mut().set_output_level(level_db);
+    }
+
+    pub fn set_asymmetry(&mut self, asymmetry: f32) {
+        self.oversampler.module_mut().set_asymmetry(asymmetry);
+    }
+
+    pub fn set_bits(&mut self, bits: f32) {
+        self.oversampler.module_mut().set_bits(bits);
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.oversampler.module_mut().set_mix(mix);
+    }
+}
+
+impl Default for Distortion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Distortion {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.oversampler.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self) {
+        self.oversampler.process();
+    }
+}
+
+impl StereoGenerator for Distortion {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.oversampler.get_left_output()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.oversampler.get_right_output()
+    }
+}
+
+impl StereoProcessor for Distortion {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Distortion::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Distortion::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::core::{DelayLine, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+use super::Lfo;
+
+const MIN_DELAY_MS: f32 = 0.2;
+const MAX_DELAY_MS: f32 = 8.0;
+
+pub struct Flanger {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    lines: [DelayLine; 2],
+    lfo: Lfo,
+    stereo_phase: f32,
+    depth: f32,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Flanger {
+    pub fn new() -> Self {
+        let sample_rate = 44_100.0;
+        let max_delay = (MAX_DELAY_MS * sample_rate / 1000.0) as usize + 1;
+        Self {
+            sample_rate,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            lines: [DelayLine::new(max_delay), DelayLine::new(max_delay)],
+            lfo: Lfo::new(0.2),
+            stereo_phase: 0.25,
+            depth: 0.7,
+            feedback: 0.5,
+            mix: 0.5,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // Hz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.lfo.set_frequency(rate.max(0.0));
+    }
+
+    // 0..1
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    // -0.95..0.95, negative values give the hollow flavour.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.95, 0.95);
+    }
+
+    // LFO phase offset of the right side, 0..1
+    pub fn set_stereo_phase(&mut self, stereo_phase: f32) {
+        self.stereo_phase = stereo_phase.clamp(0.0, 1.0);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    fn process_sample(&mut self, side: usize, x: f32) -> f32 {
+        let ms_to_samples = self.sample_rate / 1000.0;
+        let modulation = self.lfo.unipolar_value(side as f32 * self.stereo_phase);
+        let delay_ms = MIN_DELAY_MS + self.depth * (MAX_DELAY_MS - MIN_DELAY_MS) * modulation;
+        let line = &mut self.lines[side];
+        let wet = line.read_interpolated(delay_ms * ms_to_samples);
+        line.push(x + self.feedback * wet);
+        (1.0 - self.mix) * x + self.mix * wet
+    }
+}
+
+impl Default for Flanger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Flanger {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.lfo.set_sample_rate(sample_rate);
+        let max_delay = (MAX_DELAY_MS * sample_rate / 1000.0) as usize + 1;
+        self.lines.iter_mut().for_each(|l| l.resize(max_delay));
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            *out_l = self.process_sample(0, *in_l);
+            *out_r = self.process_sample(1, *in_r);
+            self.lfo.advance();
+        }
+    }
+}
+
+impl StereoGenerator for Flanger {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Flanger {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Flanger::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Flanger::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::modules::wave::{SineWave, Wave};
+
+const SIN_WAVE: SineWave = SineWave {};
+
+// Sine low frequency oscillator used to modulate effects parameters.
+pub struct Lfo {
+    sample_rate: f32,
+    frequency: f32,
+    phase: f32,
+    step: f32,
+}
+
+impl Lfo {
+    pub fn new(frequency: f32) -> Self {
+        let mut lfo = Lfo {
+            sample_rate: 44_100.0,
+            frequency,
+            phase: 0.0,
+            step: 0.0,
+        };
+        lfo.set_frequency(frequency);
+        lfo
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        self.step = frequency / self.sample_rate;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_frequency(self.frequency);
+    }
+
+    // Value in -1..1, phase_offset in 0..1
+    #[inline]
+    pub fn value(&self, phase_offset: f32) -> f32 {
+        SIN_WAVE.get_at((self.phase + phase_offset).fract())
+    }
+
+    // Value in 0..1
+    #[inline]
+    pub fn unipolar_value(&self, phase_offset: f32) -> f32 {
+        (1.0 + self.value(phase_offset)) / 2.0
+    }
+
+    #[inline]
+    pub fn advance(&mut self) {
+        self.phase += self.step;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+    }
+}
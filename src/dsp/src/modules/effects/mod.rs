@@ -0,0 +1,19 @@
+mod bitcrusher;
+mod chorus;
+mod convolution;
+mod distortion;
+mod flanger;
+mod lfo;
+mod phaser;
+mod tape;
+mod vocoder;
+
+pub use bitcrusher::*;
+pub use chorus::*;
+pub use convolution::*;
+pub use distortion::*;
+pub use flanger::*;
+pub use lfo::*;
+pub use phaser::*;
+pub use tape::*;
+pub use vocoder::*;
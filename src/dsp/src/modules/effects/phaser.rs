@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+
+use crate::core::{Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+use super::Lfo;
+
+const MAX_STAGES: usize = 12;
+
+// First order allpass filter
+#[derive(Default, Clone, Copy)]
+struct AllpassStage {
+    x1: f32,
+    y1: f32,
+}
+
+impl AllpassStage {
+    #[inline]
+    fn process(&mut self, x: f32, coef: f32) -> f32 {
+        let y = coef * x + self.x1 - coef * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+pub struct Phaser {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    stages: [[AllpassStage; MAX_STAGES]; 2],
+    last_output: [f32; 2],
+    lfo: Lfo,
+    stage_count: usize,
+    min_frequency: f32,
+    max_frequency: f32,
+    stereo_phase: f32,
+    depth: f32,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Phaser {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            stages: [[AllpassStage::default(); MAX_STAGES]; 2],
+            last_output: [0.0; 2],
+            lfo: Lfo::new(0.5),
+            stage_count: 4,
+            min_frequency: 200.0,
+            max_frequency: 4_000.0,
+            stereo_phase: 0.25,
+            depth: 1.0,
+            feedback: 0.3,
+            mix: 0.5,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // 1..12 allpass stages, each pair of stages adds a notch.
+    pub fn set_stages(&mut self, stages: usize) {
+        self.stage_count = stages.clamp(1, MAX_STAGES);
+    }
+
+    // Hz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.lfo.set_frequency(rate.max(0.0));
+    }
+
+    // Sweep range in Hz
+    pub fn set_frequency_range(&mut self, min_frequency: f32, max_frequency: f32) {
+        if min_frequency > 0.0 && min_frequency < max_frequency {
+            self.min_frequency = min_frequency;
+            self.max_frequency = max_frequency;
+        }
+    }
+
+    // 0..1
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    // -0.95..0.95
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.95, 0.95);
+    }
+
+    // LFO phase offset of the right side, 0..1
+    pub fn set_stereo_phase(&mut self, stereo_phase: f32) {
+        self.stereo_phase = stereo_phase.clamp(0.0, 1.0);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    #[inline]
+    fn allpass_coef(&self, frequency: f32) -> f32 {
+        let t = (PI * frequency.min(0.49 * self.sample_rate) / self.sample_rate).tan();
+        (t - 1.0) / (t + 1.0)
+    }
+
+    #[inline]
+    fn process_sample(&mut self, side: usize, x: f32) -> f32 {
+        // Exponential sweep between min and max frequency.
+        let range = self.max_frequency / self.min_frequency;
+        let modulation = self.depth * self.lfo.unipolar_value(side as f32 * self.stereo_phase);
+        let coef = self.allpass_coef(self.min_frequency * range.powf(modulation));
+
+        let mut wet = x + self.feedback * self.last_output[side];
+        for stage in self.stages[side][..self.stage_count].iter_mut() {
+            wet = stage.process(wet, coef);
+        }
+        self.last_output[side] = wet;
+        (1.0 - self.mix) * x + self.mix * wet
+    }
+}
+
+impl Default for Phaser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Phaser {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.lfo.set_sample_rate(sample_rate);
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            *out_l = self.process_sample(0, *in_l);
+            *out_r = self.process_sample(1, *in_r);
+            self.lfo.advance();
+        }
+    }
+}
+
+impl StereoGenerator for Phaser {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Phaser {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Phaser::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Phaser::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,228 @@
+use crate::core::{
+    db_to_gain, Biquad, BiquadCoefs, BiquadType, DelayLine, Module, SharedBuffer, StereoGenerator,
+    StereoProcessor,
+};
+
+use super::Lfo;
+
+const MAX_WOW_MS: f32 = 4.0;
+const MAX_FLUTTER_MS: f32 = 0.5;
+// Centre of the modulated delay, so it never goes below zero.
+const BASE_DELAY_MS: f32 = MAX_WOW_MS + MAX_FLUTTER_MS + 1.0;
+const MAX_DELAY_MS: f32 = BASE_DELAY_MS + MAX_WOW_MS + MAX_FLUTTER_MS + 1.0;
+const HISS_FREQUENCY: f32 = 800.0;
+
+// Worn tape: saturation, head bump, high frequency loss, wow and flutter (the same transport
+// modulates both channels) and optional hiss.
+pub struct Tape {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    lines: [DelayLine; 2],
+    // The dry signal is delayed by the base delay of the tape, so the mix does not comb.
+    dry_lines: [DelayLine; 2],
+    wow: Lfo,
+    // Slower, incommensurate component so the wow does not sound periodic.
+    drift: Lfo,
+    flutter: Lfo,
+    wow_depth: f32,
+    wow_rate: f32,
+    flutter_depth: f32,
+    drive: f32,
+    drive_normalization: f32,
+    head_bump_frequency: f32,
+    head_bump_gain_db: f32,
+    high_cut: f32,
+    head_bump: [Biquad; 2],
+    high_loss: [Biquad; 2],
+    hiss_gain: Option<f32>,
+    hiss_filters: [Biquad; 2],
+    mix: f32,
+}
+
+impl Tape {
+    pub fn new() -> Self {
+        let sample_rate = 44_100.0;
+        let max_delay = (MAX_DELAY_MS * sample_rate / 1000.0) as usize;
+        let mut tape = Self {
+            sample_rate,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            lines: [DelayLine::new(max_delay), DelayLine::new(max_delay)],
+            dry_lines: [DelayLine::new(max_delay), DelayLine::new(max_delay)],
+            wow: Lfo::new(0.5),
+            drift: Lfo::new(0.5 * 0.37),
+            flutter: Lfo::new(6.0),
+            wow_depth: 0.2,
+            wow_rate: 0.5,
+            flutter_depth: 0.2,
+            drive: 1.0,
+            drive_normalization: 1.0,
+            head_bump_frequency: 80.0,
+            head_bump_gain_db: 3.0,
+            high_cut: 12_000.0,
+            head_bump: [Biquad::default(); 2],
+            high_loss: [Biquad::default(); 2],
+            hiss_gain: None,
+            hiss_filters: [Biquad::default(); 2],
+            mix: 1.0,
+        };
+        tape.set_drive(6.0);
+        tape.update_filters();
+        tape
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // Slow pitch drift: depth 0..1 (up to 4ms of delay modulation), rate in Hz (0.05..4)
+    pub fn set_wow(&mut self, depth: f32, rate: f32) {
+        self.wow_depth = depth.clamp(0.0, 1.0);
+        self.wow_rate = rate.clamp(0.05, 4.0);
+        self.wow.set_frequency(self.wow_rate);
+        self.drift.set_frequency(self.wow_rate * 0.37);
+    }
+
+    // Fast pitch modulation: depth 0..1 (up to 0.5ms of delay modulation), rate in Hz (2..20)
+    pub fn set_flutter(&mut self, depth: f32, rate: f32) {
+        self.flutter_depth = depth.clamp(0.0, 1.0);
+        self.flutter.set_frequency(rate.clamp(2.0, 20.0));
+    }
+
+    // Saturation drive, 0..24 dB. Full scale input stays at full scale.
+    pub fn set_drive(&mut self, drive_db: f32) {
+        self.drive = db_to_gain(drive_db.clamp(0.0, 24.0));
+        self.drive_normalization = 1.0 / self.drive.tanh();
+    }
+
+    // Low frequency resonance of the playback head.
+    pub fn set_head_bump(&mut self, frequency: f32, gain_db: f32) {
+        self.head_bump_frequency = frequency.clamp(30.0, 200.0);
+        self.head_bump_gain_db = gain_db.clamp(0.0, 6.0);
+        self.update_filters();
+    }
+
+    // High frequency loss: cutoff of the tape response, Hz
+    pub fn set_high_cut(&mut self, frequency: f32) {
+        self.high_cut = frequency.clamp(1_000.0, 20_000.0);
+        self.update_filters();
+    }
+
+    // Hiss level in dB, None to disable
+    pub fn set_hiss(&mut self, level_db: Option<f32>) {
+        self.hiss_gain = level_db.map(db_to_gain);
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update_filters(&mut self) {
+        let sample_rate = self.sample_rate;
+        let bump = BiquadCoefs::new(
+            BiquadType::Peak,
+            sample_rate,
+            self.head_bump_frequency,
+            1.0,
+            self.head_bump_gain_db,
+        );
+        let loss = BiquadCoefs::new(BiquadType::LowPass, sample_rate, self.high_cut, 0.6, 0.0);
+        let hiss = BiquadCoefs::new(BiquadType::HighPass, sample_rate, HISS_FREQUENCY, 0.707, 0.0);
+        self.head_bump.iter_mut().for_each(|f| f.set_coefs(bump));
+        self.high_loss.iter_mut().for_each(|f| f.set_coefs(loss));
+        self.hiss_filters.iter_mut().for_each(|f| f.set_coefs(hiss));
+    }
+
+    #[inline]
+    fn process_sample(&mut self, side: usize, x: f32, delay: f32, base_delay: f32) -> f32 {
+        let saturated = (self.drive * x).tanh() * self.drive_normalization;
+        let colored = self.high_loss[side].process(self.head_bump[side].process(saturated));
+        let line = &mut self.lines[side];
+        line.push(colored);
+        let mut wet = line.read_interpolated(delay);
+        if let Some(hiss_gain) = self.hiss_gain {
+            wet += hiss_gain * self.hiss_filters[side].process(2.0 * fastrand::f32() - 1.0);
+        }
+        let dry_line = &mut self.dry_lines[side];
+        dry_line.push(x);
+        let dry = dry_line.read_interpolated(base_delay);
+        (1.0 - self.mix) * dry + self.mix * wet
+    }
+}
+
+impl Default for Tape {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Tape {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.wow.set_sample_rate(sample_rate);
+        self.drift.set_sample_rate(sample_rate);
+        self.flutter.set_sample_rate(sample_rate);
+        let max_delay = (MAX_DELAY_MS * sample_rate / 1000.0) as usize;
+        self.lines.iter_mut().chain(self.dry_lines.iter_mut()).for_each(|l| l.resize(max_delay));
+        self.update_filters();
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let ms_to_samples = self.sample_rate / 1000.0;
+        let base_delay = BASE_DELAY_MS * ms_to_samples;
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            let wow = 0.7 * self.wow.value(0.0) + 0.3 * self.drift.value(0.0);
+            let delay_ms = BASE_DELAY_MS
+                + self.wow_depth * MAX_WOW_MS * wow
+                + self.flutter_depth * MAX_FLUTTER_MS * self.flutter.value(0.0);
+            let delay = delay_ms * ms_to_samples;
+
+            *out_l = self.process_sample(0, *in_l, delay, base_delay);
+            *out_r = self.process_sample(1, *in_r, delay, base_delay);
+
+            self.wow.advance();
+            self.drift.advance();
+            self.flutter.advance();
+        }
+    }
+}
+
+impl StereoGenerator for Tape {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Tape {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Tape::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Tape::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,236 @@
+use crate::core::{
+    Biquad, BiquadCoefs, BiquadType, Module, SharedBuffer, StereoGenerator, StereoProcessor,
+};
+use crate::modules::dynamics::{DetectionMode, EnvelopeDetector};
+use crate::modules::filters::{BandSpacing, FilterBank, MAX_FILTER_BANK_BANDS};
+
+const UNVOICED_FREQUENCY: f32 = 5_000.0;
+
+// Channel vocoder: the band envelopes of the modulator are applied to the same bands of the
+// (stereo) carrier. Noise can be injected in the carrier on unvoiced (sibilant) parts of the
+// modulator so consonants stay intelligible with a tonal carrier.
+pub struct Vocoder {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    input_modulator: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    band_count: usize,
+    min_frequency: f32,
+    max_frequency: f32,
+    spacing: BandSpacing,
+    attack_ms: f32,
+    release_ms: f32,
+    modulator_bank: FilterBank,
+    carrier_banks: [FilterBank; 2],
+    envelopes: Vec<EnvelopeDetector>,
+    band_gain: f32,
+    unvoiced_filter: Biquad,
+    unvoiced_envelope: EnvelopeDetector,
+    modulator_envelope: EnvelopeDetector,
+    noise_level: f32,
+    mix: f32,
+    modulator_bands: Vec<f32>,
+    carrier_bands: Vec<f32>,
+}
+
+impl Vocoder {
+    pub fn new() -> Self {
+        let band_count = 16;
+        let (min_frequency, max_frequency) = (80.0, 12_000.0);
+        let spacing = BandSpacing::Logarithmic;
+        let bank = || FilterBank::new(band_count, min_frequency, max_frequency, spacing);
+        let mut vocoder = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            input_modulator: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            band_count,
+            min_frequency,
+            max_frequency,
+            spacing,
+            attack_ms: 5.0,
+            release_ms: 50.0,
+            modulator_bank: bank(),
+            carrier_banks: [bank(), bank()],
+            envelopes: vec![],
+            band_gain: 1.0,
+            unvoiced_filter: Biquad::default(),
+            unvoiced_envelope: EnvelopeDetector::new(DetectionMode::Peak, 1.0, 30.0),
+            modulator_envelope: EnvelopeDetector::new(DetectionMode::Peak, 1.0, 30.0),
+            noise_level: 0.0,
+            mix: 1.0,
+            modulator_bands: vec![0.0; MAX_FILTER_BANK_BANDS],
+            carrier_bands: vec![0.0; MAX_FILTER_BANK_BANDS],
+        };
+        vocoder.update_bands();
+        Module::set_sample_rate(&mut vocoder, 44_100.0);
+        vocoder
+    }
+
+    // Carrier
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    // Carrier
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    pub fn set_modulator_input(&mut self, input: SharedBuffer) {
+        self.input_modulator = input;
+    }
+
+    // 4..64
+    pub fn set_band_count(&mut self, band_count: usize) {
+        self.band_count = band_count.clamp(4, MAX_FILTER_BANK_BANDS);
+        self.update_bands();
+    }
+
+    // Hz
+    pub fn set_frequency_range(&mut self, min_frequency: f32, max_frequency: f32) {
+        if min_frequency > 0.0 && min_frequency < max_frequency {
+            self.min_frequency = min_frequency;
+            self.max_frequency = max_frequency;
+            self.update_bands();
+        }
+    }
+
+    pub fn set_spacing(&mut self, spacing: BandSpacing) {
+        self.spacing = spacing;
+        self.update_bands();
+    }
+
+    // ms
+    pub fn set_attack(&mut self, attack_ms: f32) {
+        self.attack_ms = attack_ms.max(0.0);
+        self.envelopes.iter_mut().for_each(|e| e.set_attack(attack_ms));
+    }
+
+    // ms
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.max(0.0);
+        self.envelopes.iter_mut().for_each(|e| e.set_release(release_ms));
+    }
+
+    // Shifts the carrier bands, -12..12 semitones
+    pub fn set_formant_shift(&mut self, semi_tones: f32) {
+        let ratio = 2.0_f32.powf(semi_tones.clamp(-12.0, 12.0) / 12.0);
+        self.carrier_banks.iter_mut().for_each(|b| b.set_frequency_ratio(ratio));
+    }
+
+    // Noise added to the carrier on unvoiced parts of the modulator, 0..1
+    pub fn set_noise_level(&mut self, noise_level: f32) {
+        self.noise_level = noise_level.clamp(0.0, 1.0);
+    }
+
+    // 0: carrier only, 1: vocoded only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update_bands(&mut self) {
+        let (count, min, max, spacing) = (self.band_count, self.min_frequency, self.max_frequency, self.spacing);
+        self.modulator_bank.set_bands(count, min, max, spacing);
+        self.carrier_banks.iter_mut().for_each(|b| b.set_bands(count, min, max, spacing));
+        self.envelopes = (0..count)
+            .map(|_| {
+                let mut envelope = EnvelopeDetector::new(DetectionMode::Peak, self.attack_ms, self.release_ms);
+                envelope.set_sample_rate(self.sample_rate);
+                envelope
+            })
+            .collect();
+        // Each band passes about 1/count of the power of both signals: a full scale modulator
+        // gives an output close to the carrier level whatever the band count.
+        self.band_gain = 8.0 * (count as f32).sqrt();
+    }
+}
+
+impl Default for Vocoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Vocoder {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.modulator_bank.set_sample_rate(sample_rate);
+        self.carrier_banks.iter_mut().for_each(|b| b.set_sample_rate(sample_rate));
+        self.envelopes.iter_mut().for_each(|e| e.set_sample_rate(sample_rate));
+        self.unvoiced_envelope.set_sample_rate(sample_rate);
+        self.modulator_envelope.set_sample_rate(sample_rate);
+        self.unvoiced_filter.set_coefs(BiquadCoefs::new(
+            BiquadType::HighPass,
+            sample_rate,
+            UNVOICED_FREQUENCY,
+            0.707,
+            0.0,
+        ));
+    }
+
+    fn process(&mut self) {
+        let (input_left, input_right) = (self.input_left.clone(), self.input_right.clone());
+        let input_modulator = self.input_modulator.clone();
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let input_left = input_left.try_borrow().unwrap();
+        let input_right = input_right.try_borrow().unwrap();
+        let input_modulator = input_modulator.try_borrow().unwrap();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+
+        let count = self.band_count;
+        let inputs = input_left.get().iter().zip(input_right.get()).zip(input_modulator.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+
+        for (((in_l, in_r), modulator), (out_l, out_r)) in inputs.zip(outputs) {
+            self.modulator_bank.process(*modulator, &mut self.modulator_bands);
+            for (envelope, band) in self.envelopes.iter_mut().zip(&self.modulator_bands[..count]) {
+                envelope.process(*band);
+            }
+
+            // Ratio of high frequency content: close to 1 on sibilants.
+            let unvoiced = self.unvoiced_envelope.process(self.unvoiced_filter.process(*modulator));
+            let total = self.modulator_envelope.process(*modulator);
+            let noise = if self.noise_level > 0.0 && total > 1e-6 {
+                self.noise_level * (unvoiced / total).min(1.0) * (2.0 * fastrand::f32() - 1.0)
+            } else {
+                0.0
+            };
+
+            for (side, (x, out)) in [(*in_l, out_l), (*in_r, out_r)].iter_mut().enumerate() {
+                self.carrier_banks[side].process(*x + noise, &mut self.carrier_bands);
+                let vocoded: f32 = self.carrier_bands[..count]
+                    .iter()
+                    .zip(&self.envelopes)
+                    .map(|(c, e)| c * e.value())
+                    .sum();
+                **out = (1.0 - self.mix) * *x + self.mix * self.band_gain * vocoded;
+            }
+        }
+    }
+}
+
+impl StereoGenerator for Vocoder {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Vocoder {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Vocoder::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Vocoder::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,264 @@
+use crate::core::{
+    gain_to_db, time_constant_coef, Biquad, BiquadCoefs, BiquadType, Module, SharedBuffer,
+    StereoGenerator, StereoProcessor,
+};
+
+pub const MAX_EQ_BANDS: usize = 8;
+// Coefficients are recomputed every SMOOTHING_BLOCK samples while a parameter moves.
+const SMOOTHING_BLOCK: usize = 8;
+const SMOOTHING_TIME_MS: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EqBandType {
+    Bell,
+    LowShelf,
+    HighShelf,
+    HighPass,
+    LowPass,
+    Notch,
+}
+
+impl EqBandType {
+    fn biquad_type(&self) -> BiquadType {
+        match self {
+            EqBandType::Bell => BiquadType::Peak,
+            EqBandType::LowShelf => BiquadType::LowShelf,
+            EqBandType::HighShelf => BiquadType::HighShelf,
+            EqBandType::HighPass => BiquadType::HighPass,
+            EqBandType::LowPass => BiquadType::LowPass,
+            EqBandType::Notch => BiquadType::Notch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub kind: EqBandType,
+    pub frequency: f32,
+    pub gain_db: f32,
+    pub q: f32,
+    pub enabled: bool,
+}
+
+impl EqBand {
+    fn coefs(&self, sample_rate: f32) -> BiquadCoefs {
+        BiquadCoefs::new(
+            self.kind.biquad_type(),
+            sample_rate,
+            self.frequency,
+            self.q,
+            self.gain_db,
+        )
+    }
+}
+
+impl Default for EqBand {
+    fn default() -> Self {
+        EqBand {
+            kind: EqBandType::Bell,
+            frequency: 1_000.0,
+            gain_db: 0.0,
+            q: std::f32::consts::FRAC_1_SQRT_2,
+            enabled: false,
+        }
+    }
+}
+
+// Response (in dB) of the enabled bands at the given frequencies, to draw EQ curves.
+pub fn eq_magnitude_response(bands: &[EqBand], sample_rate: f32, frequencies: &[f32]) -> Vec<f32> {
+    let coefs: Vec<BiquadCoefs> = bands
+        .iter()
+        .filter(|b| b.enabled)
+        .map(|b| b.coefs(sample_rate))
+        .collect();
+    frequencies
+        .iter()
+        .map(|f| {
+            let magnitude: f32 = coefs.iter().map(|c| c.magnitude(*f, sample_rate)).product();
+            gain_to_db(magnitude)
+        })
+        .collect()
+}
+
+struct EqBandState {
+    target: EqBand,
+    // Smoothed parameters, frequency in log2
+    log_frequency: f32,
+    gain_db: f32,
+    q: f32,
+    filters: [Biquad; 2],
+}
+
+impl EqBandState {
+    fn new() -> Self {
+        let target = EqBand::default();
+        EqBandState {
+            log_frequency: target.frequency.log2(),
+            gain_db: target.gain_db,
+            q: target.q,
+            target,
+            filters: [Biquad::default(); 2],
+        }
+    }
+
+    fn current(&self) -> EqBand {
+        EqBand {
+            frequency: self.log_frequency.exp2(),
+            gain_db: self.gain_db,
+            q: self.q,
+            ..self.target
+        }
+    }
+
+    // Returns true while the parameters move.
+    fn smooth(&mut self, coef: f32) -> bool {
+        let target_log_frequency = self.target.frequency.log2();
+        let moving = (self.log_frequency - target_log_frequency).abs() > 1e-4
+            || (self.gain_db - self.target.gain_db).abs() > 1e-3
+            || (self.q - self.target.q).abs() > 1e-4;
+        if moving {
+            self.log_frequency = target_log_frequency + coef * (self.log_frequency - target_log_frequency);
+            self.gain_db = self.target.gain_db + coef * (self.gain_db - self.target.gain_db);
+            self.q = self.target.q + coef * (self.q - self.target.q);
+        }
+        moving
+    }
+
+    fn update_coefs(&mut self, sample_rate: f32) {
+        let coefs = self.current().coefs(sample_rate);
+        self.filters.iter_mut().for_each(|f| f.set_coefs(coefs));
+    }
+
+    // Jump to the target without smoothing.
+    fn snap(&mut self, sample_rate: f32) {
+        self.log_frequency = self.target.frequency.log2();
+        self.gain_db = self.target.gain_db;
+        self.q = self.target.q;
+        self.update_coefs(sample_rate);
+    }
+}
+
+pub struct Equalizer {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    bands: Vec<EqBandState>,
+    smoothing_coef: f32,
+}
+
+impl Equalizer {
+    pub fn new() -> Self {
+        let mut eq = Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            bands: (0..MAX_EQ_BANDS).map(|_| EqBandState::new()).collect(),
+            smoothing_coef: 0.0,
+        };
+        eq.set_sample_rate(44_100.0);
+        eq
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // Frequency, gain and q changes are smoothed, type changes are immediate.
+    pub fn set_band(&mut self, index: usize, band: EqBand) {
+        let sample_rate = self.sample_rate;
+        if let Some(state) = self.bands.get_mut(index) {
+            let band = EqBand {
+                frequency: band.frequency.clamp(10.0, 0.49 * sample_rate),
+                q: band.q.clamp(0.05, 40.0),
+                gain_db: band.gain_db.clamp(-36.0, 36.0),
+                ..band
+            };
+            let kind_changed = state.target.kind != band.kind;
+            let enabled = !state.target.enabled && band.enabled;
+            state.target = band;
+            if kind_changed || enabled {
+                state.filters.iter_mut().for_each(|f| f.reset());
+                state.snap(sample_rate);
+            }
+        }
+    }
+
+    pub fn band(&self, index: usize) -> Option<EqBand> {
+        self.bands.get(index).map(|b| b.target)
+    }
+
+    pub fn bands(&self) -> Vec<EqBand> {
+        self.bands.iter().map(|b| b.target).collect()
+    }
+
+    pub fn magnitude_response(&self, frequencies: &[f32]) -> Vec<f32> {
+        eq_magnitude_response(&self.bands(), self.sample_rate, frequencies)
+    }
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Equalizer {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.smoothing_coef =
+            time_constant_coef(SMOOTHING_TIME_MS, sample_rate / SMOOTHING_BLOCK as f32);
+        self.bands.iter_mut().for_each(|b| b.snap(sample_rate));
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+        output_left.get_mut().copy_from_slice(input_left.get());
+        output_right.get_mut().copy_from_slice(input_right.get());
+
+        let blocks = output_left
+            .get_mut()
+            .chunks_mut(SMOOTHING_BLOCK)
+            .zip(output_right.get_mut().chunks_mut(SMOOTHING_BLOCK));
+        for (block_left, block_right) in blocks {
+            for band in self.bands.iter_mut().filter(|b| b.target.enabled) {
+                if band.smooth(self.smoothing_coef) {
+                    band.update_coefs(self.sample_rate);
+                }
+                let [filter_left, filter_right] = &mut band.filters;
+                block_left.iter_mut().for_each(|v| *v = filter_left.process(*v));
+                block_right.iter_mut().for_each(|v| *v = filter_right.process(*v));
+            }
+        }
+    }
+}
+
+impl StereoGenerator for Equalizer {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for Equalizer {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        Equalizer::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        Equalizer::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::core::{Biquad, BiquadCoefs, BiquadType};
+
+pub const MAX_FILTER_BANK_BANDS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BandSpacing {
+    Logarithmic,
+    // Critical bands of hearing, denser in the low mids than a log spacing.
+    Bark,
+}
+
+// Traunmüller approximation of the bark scale.
+fn hz_to_bark(frequency: f32) -> f32 {
+    26.81 * frequency / (1960.0 + frequency) - 0.53
+}
+
+fn bark_to_hz(bark: f32) -> f32 {
+    1960.0 * (bark + 0.53) / (26.28 - bark)
+}
+
+// Band edges (band_count + 1 values) between min and max frequency.
+pub fn band_edges(band_count: usize, min_frequency: f32, max_frequency: f32, spacing: BandSpacing) -> Vec<f32> {
+    (0..=band_count)
+        .map(|i| {
+            let t = i as f32 / band_count as f32;
+            match spacing {
+                BandSpacing::Logarithmic => min_frequency * (max_frequency / min_frequency).powf(t),
+                BandSpacing::Bark => {
+                    let low = hz_to_bark(min_frequency);
+                    let high = hz_to_bark(max_frequency);
+                    bark_to_hz(low + t * (high - low))
+                }
+            }
+        })
+        .collect()
+}
+
+struct Band {
+    center: f32,
+    q: f32,
+    filters: [Biquad; 2],
+}
+
+// Bank of 4th order band-pass filters splitting a signal into adjacent bands.
+pub struct FilterBank {
+    sample_rate: f32,
+    spacing: BandSpacing,
+    min_frequency: f32,
+    max_frequency: f32,
+    frequency_ratio: f32,
+    bands: Vec<Band>,
+}
+
+impl FilterBank {
+    pub fn new(band_count: usize, min_frequency: f32, max_frequency: f32, spacing: BandSpacing) -> Self {
+        let mut bank = FilterBank {
+            sample_rate: 44_100.0,
+            spacing,
+            min_frequency,
+            max_frequency,
+            frequency_ratio: 1.0,
+            bands: vec![],
+        };
+        bank.set_bands(band_count, min_frequency, max_frequency, spacing);
+        bank
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coefs();
+    }
+
+    // 1..64 bands
+    pub fn set_bands(&mut self, band_count: usize, min_frequency: f32, max_frequency: f32, spacing: BandSpacing) {
+        let band_count = band_count.clamp(1, MAX_FILTER_BANK_BANDS);
+        if min_frequency <= 0.0 || min_frequency >= max_frequency {
+            return;
+        }
+        self.spacing = spacing;
+        self.min_frequency = min_frequency;
+        self.max_frequency = max_frequency;
+
+        let edges = band_edges(band_count, min_frequency, max_frequency, spacing);
+        self.bands = edges
+            .windows(2)
+            .map(|edge| {
+                let center = (edge[0] * edge[1]).sqrt();
+                Band {
+                    center,
+                    q: center / (edge[1] - edge[0]),
+                    filters: [Biquad::default(), Biquad::default()],
+                }
+            })
+            .collect();
+        self.update_coefs();
+    }
+
+    // Scales all the center frequencies (e.g. formant shift), 0.25..4
+    pub fn set_frequency_ratio(&mut self, ratio: f32) {
+        self.frequency_ratio = ratio.clamp(0.25, 4.0);
+        self.update_coefs();
+    }
+
+    pub fn spacing(&self) -> BandSpacing {
+        self.spacing
+    }
+
+    pub fn band_count(&self) -> usize {
+        self.bands.len()
+    }
+
+    pub fn center_frequency(&self, band: usize) -> f32 {
+        self.bands[band].center * self.frequency_ratio
+    }
+
+    pub fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.filters.iter_mut().for_each(|f| f.reset());
+        }
+    }
+
+    fn update_coefs(&mut self) {
+        let sample_rate = self.sample_rate;
+        let ratio = self.frequency_ratio;
+        for band in self.bands.iter_mut() {
+            let coefs = BiquadCoefs::new(BiquadType::BandPass, sample_rate, band.center * ratio, band.q, 0.0);
+            band.filters.iter_mut().for_each(|f| f.set_coefs(coefs));
+        }
+    }
+
+    // Writes the output of each band into `outputs` (at least band_count long).
+    #[inline]
+    pub fn process(&mut self, x: f32, outputs: &mut [f32]) {
+        for (band, output) in self.bands.iter_mut().zip(outputs.iter_mut()) {
+            let [first, second] = &mut band.filters;
+            *output = second.process(first.process(x));
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use crate::core::{
+    db_to_gain, time_constant_coef, Biquad, BiquadCoefs, BiquadType, Module, SharedBuffer,
+    StereoGenerator, StereoProcessor,
+};
+
+pub const FORMANT_COUNT: usize = 5;
+// Coefficients are recomputed every SMOOTHING_BLOCK samples while the morph moves.
+const SMOOTHING_BLOCK: usize = 8;
+const SMOOTHING_TIME_MS: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceType {
+    Soprano,
+    Alto,
+    CounterTenor,
+    Tenor,
+    Bass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+impl Vowel {
+    // Position of the vowel on the morph axis.
+    pub fn morph(&self) -> f32 {
+        match self {
+            Vowel::A => 0.0,
+            Vowel::E => 1.0,
+            Vowel::I => 2.0,
+            Vowel::O => 3.0,
+            Vowel::U => 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Formant {
+    pub frequency: f32,
+    pub gain_db: f32,
+    pub bandwidth: f32,
+}
+
+// (frequencies, gains in dB, bandwidths) for a, e, i, o, u
+type VowelTable = [([f32; FORMANT_COUNT], [f32; FORMANT_COUNT], [f32; FORMANT_COUNT]); 5];
+
+const SOPRANO: VowelTable = [
+    ([800.0, 1150.0, 2900.0, 3900.0, 4950.0], [0.0, -6.0, -32.0, -20.0, -50.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+    ([350.0, 2000.0, 2800.0, 3600.0, 4950.0], [0.0, -20.0, -15.0, -40.0, -56.0], [60.0, 100.0, 120.0, 150.0, 200.0]),
+    ([270.0, 2140.0, 2950.0, 3900.0, 4950.0], [0.0, -12.0, -26.0, -26.0, -44.0], [60.0, 90.0, 100.0, 120.0, 120.0]),
+    ([450.0, 800.0, 2830.0, 3800.0, 4950.0], [0.0, -11.0, -22.0, -22.0, -50.0], [70.0, 80.0, 100.0, 130.0, 135.0]),
+    ([325.0, 700.0, 2700.0, 3800.0, 4950.0], [0.0, -16.0, -35.0, -40.0, -60.0], [50.0, 60.0, 170.0, 180.0, 200.0]),
+];
+
+const ALTO: VowelTable = [
+    ([800.0, 1150.0, 2800.0, 3500.0, 4950.0], [0.0, -4.0, -20.0, -36.0, -60.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+    ([400.0, 1600.0, 2700.0, 3300.0, 4950.0], [0.0, -24.0, -30.0, -35.0, -60.0], [60.0, 80.0, 120.0, 150.0, 200.0]),
+    ([350.0, 1700.0, 2700.0, 3700.0, 4950.0], [0.0, -20.0, -30.0, -36.0, -60.0], [50.0, 100.0, 120.0, 150.0, 200.0]),
+    ([450.0, 800.0, 2830.0, 3500.0, 4950.0], [0.0, -9.0, -16.0, -28.0, -55.0], [70.0, 80.0, 100.0, 130.0, 135.0]),
+    ([325.0, 700.0, 2530.0, 3500.0, 4950.0], [0.0, -12.0, -30.0, -40.0, -64.0], [50.0, 60.0, 170.0, 180.0, 200.0]),
+];
+
+const COUNTER_TENOR: VowelTable = [
+    ([660.0, 1120.0, 2750.0, 3000.0, 3350.0], [0.0, -6.0, -23.0, -24.0, -38.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+    ([440.0, 1800.0, 2700.0, 3000.0, 3300.0], [0.0, -14.0, -18.0, -20.0, -20.0], [70.0, 80.0, 100.0, 120.0, 120.0]),
+    ([270.0, 1850.0, 2900.0, 3350.0, 3590.0], [0.0, -24.0, -24.0, -36.0, -36.0], [40.0, 90.0, 100.0, 120.0, 120.0]),
+    ([430.0, 820.0, 2700.0, 3000.0, 3300.0], [0.0, -10.0, -26.0, -22.0, -34.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+    ([370.0, 630.0, 2750.0, 3000.0, 3400.0], [0.0, -20.0, -23.0, -30.0, -34.0], [40.0, 60.0, 100.0, 120.0, 120.0]),
+];
+
+const TENOR: VowelTable = [
+    ([650.0, 1080.0, 2650.0, 2900.0, 3250.0], [0.0, -6.0, -7.0, -8.0, -22.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+    ([400.0, 1700.0, 2600.0, 3200.0, 3580.0], [0.0, -14.0, -12.0, -14.0, -20.0], [70.0, 80.0, 100.0, 120.0, 120.0]),
+    ([290.0, 1870.0, 2800.0, 3250.0, 3540.0], [0.0, -15.0, -18.0, -20.0, -30.0], [40.0, 90.0, 100.0, 120.0, 120.0]),
+    ([400.0, 800.0, 2600.0, 2800.0, 3000.0], [0.0, -10.0, -12.0, -12.0, -26.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+    ([350.0, 600.0, 2700.0, 2900.0, 3300.0], [0.0, -20.0, -17.0, -14.0, -26.0], [40.0, 60.0, 100.0, 120.0, 120.0]),
+];
+
+const BASS: VowelTable = [
+    ([600.0, 1040.0, 2250.0, 2450.0, 2750.0], [0.0, -7.0, -9.0, -9.0, -20.0], [60.0, 70.0, 110.0, 120.0, 130.0]),
+    ([400.0, 1620.0, 2400.0, 2800.0, 3100.0], [0.0, -12.0, -9.0, -12.0, -18.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+    ([250.0, 1750.0, 2600.0, 3050.0, 3340.0], [0.0, -30.0, -16.0, -22.0, -28.0], [60.0, 90.0, 100.0, 120.0, 120.0]),
+    ([400.0, 750.0, 2400.0, 2600.0, 2900.0], [0.0, -11.0, -21.0, -20.0, -40.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+    ([350.0, 600.0, 2400.0, 2675.0, 2950.0], [0.0, -20.0, -32.0, -28.0, -36.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+];
+
+fn vowel_table(voice: VoiceType) -> &'static VowelTable {
+    match voice {
+        VoiceType::Soprano => &SOPRANO,
+        VoiceType::Alto => &ALTO,
+        VoiceType::CounterTenor => &COUNTER_TENOR,
+        VoiceType::Tenor => &TENOR,
+        VoiceType::Bass => &BASS,
+    }
+}
+
+pub fn vowel_formants(voice: VoiceType, vowel: Vowel) -> [Formant; FORMANT_COUNT] {
+    morph_formants(voice, vowel.morph())
+}
+
+// Formants interpolated between successive vowels: 0 = a, 1 = e, 2 = i, 3 = o, 4 = u.
+// Frequencies are interpolated on a log scale.
+pub fn morph_formants(voice: VoiceType, morph: f32) -> [Formant; FORMANT_COUNT] {
+    let table = vowel_table(voice);
+    let morph = morph.clamp(0.0, 4.0);
+    let index = (morph as usize).min(3);
+    let t = morph - index as f32;
+    let (from, to) = (&table[index], &table[index + 1]);
+
+    let mut formants = [Formant {
+        frequency: 0.0,
+        gain_db: 0.0,
+        bandwidth: 0.0,
+    }; FORMANT_COUNT];
+    for (i, formant) in formants.iter_mut().enumerate() {
+        formant.frequency = from.0[i] * (to.0[i] / from.0[i]).powf(t);
+        formant.gain_db = from.1[i] + t * (to.1[i] - from.1[i]);
+        formant.bandwidth = from.2[i] + t * (to.2[i] - from.2[i]);
+    }
+    formants
+}
+
+// Mono bank of parallel band-pass filters tuned on the formants of a vowel.
+// Usable on its own per voice, FormantFilter wraps two of them as a stereo stage.
+pub struct FormantBank {
+    sample_rate: f32,
+    voice: VoiceType,
+    current_morph: f32,
+    morph: f32,
+    resonance: f32,
+    smoothing_coef: f32,
+    counter: usize,
+    filters: [Biquad; FORMANT_COUNT],
+    gains: [f32; FORMANT_COUNT],
+}
+
+impl FormantBank {
+    pub fn new(voice: VoiceType) -> Self {
+        let mut bank = FormantBank {
+            sample_rate: 44_100.0,
+            voice,
+            current_morph: 0.0,
+            morph: 0.0,
+            resonance: 1.0,
+            smoothing_coef: 0.0,
+            counter: 0,
+            filters: [Biquad::default(); FORMANT_COUNT],
+            gains: [0.0; FORMANT_COUNT],
+        };
+        bank.set_sample_rate(44_100.0);
+        bank
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.smoothing_coef = time_constant_coef(SMOOTHING_TIME_MS, sample_rate / SMOOTHING_BLOCK as f32);
+        self.update_coefs();
+    }
+
+    pub fn set_voice(&mut self, voice: VoiceType) {
+        self.voice = voice;
+        self.update_coefs();
+    }
+
+    pub fn voice(&self) -> VoiceType {
+        self.voice
+    }
+
+    pub fn set_vowel(&mut self, vowel: Vowel) {
+        self.set_morph(vowel.morph());
+    }
+
+    // 0 (a)..4 (u), smoothed
+    pub fn set_morph(&mut self, morph: f32) {
+        self.morph = morph.clamp(0.0, 4.0);
+    }
+
+    pub fn morph(&self) -> f32 {
+        self.morph
+    }
+
+    // Scales the q of all the formants, 0.25..4
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance.clamp(0.25, 4.0);
+        self.update_coefs();
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(|f| f.reset());
+    }
+
+    fn update_coefs(&mut self) {
+        let formants = morph_formants(self.voice, self.current_morph);
+        for ((filter, gain), formant) in self.filters.iter_mut().zip(self.gains.iter_mut()).zip(&formants) {
+            let q = self.resonance * formant.frequency / formant.bandwidth;
+            filter.set_coefs(BiquadCoefs::new(BiquadType::BandPass, self.sample_rate, formant.frequency, q, 0.0));
+            *gain = db_to_gain(formant.gain_db);
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        if self.counter == 0 {
+            self.counter = SMOOTHING_BLOCK;
+            if (self.current_morph - self.morph).abs() > 1e-4 {
+                self.current_morph = self.morph + self.smoothing_coef * (self.current_morph - self.morph);
+                self.update_coefs();
+            }
+        }
+        self.counter -= 1;
+
+        self.filters
+            .iter_mut()
+            .zip(&self.gains)
+            .map(|(filter, gain)| gain * filter.process(x))
+            .sum()
+    }
+}
+
+// Stereo formant filter stage.
+pub struct FormantFilter {
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    banks: [FormantBank; 2],
+    mix: f32,
+}
+
+impl FormantFilter {
+    pub fn new() -> Self {
+        Self {
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            banks: [FormantBank::new(VoiceType::Soprano), FormantBank::new(VoiceType::Soprano)],
+            mix: 1.0,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    pub fn set_voice(&mut self, voice: VoiceType) {
+        self.banks.iter_mut().for_each(|b| b.set_voice(voice));
+    }
+
+    pub fn set_vowel(&mut self, vowel: Vowel) {
+        self.banks.iter_mut().for_each(|b| b.set_vowel(vowel));
+    }
+
+    // 0 (a)..4 (u)
+    pub fn set_morph(&mut self, morph: f32) {
+        self.banks.iter_mut().for_each(|b| b.set_morph(morph));
+    }
+
+    // 0.25..4
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.banks.iter_mut().for_each(|b| b.set_resonance(resonance));
+    }
+
+    // 0: dry only, 1: wet only
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Default for FormantFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for FormantFilter {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.banks.iter_mut().for_each(|b| b.set_sample_rate(sample_rate));
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+
+        let mix = self.mix;
+        let [bank_left, bank_right] = &mut self.banks;
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            *out_l = (1.0 - mix) * in_l + mix * bank_left.process(*in_l);
+            *out_r = (1.0 - mix) * in_r + mix * bank_right.process(*in_r);
+        }
+    }
+}
+
+impl StereoGenerator for FormantFilter {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for FormantFilter {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        FormantFilter::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        FormantFilter::set_right_input(self, input)
+    }
+}
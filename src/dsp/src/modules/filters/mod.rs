@@ -0,0 +1,7 @@
+mod equalizer;
+mod filter_bank;
+mod formant;
+
+pub use equalizer::*;
+pub use filter_bank::*;
+pub use formant::*;
@@ -1,3 +1,9 @@
+pub mod analysis;
+pub mod control;
+pub mod dynamics;
+pub mod effects;
+pub mod filters;
 pub mod ops;
 pub mod oscillators;
+pub mod stereo;
 pub mod wave;
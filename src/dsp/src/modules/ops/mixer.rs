@@ -0,0 +1,275 @@
+use crate::core::{db_to_gain, Module, SharedBuffer, StereoGenerator};
+use crate::modules::oscillators::get_pan;
+
+#[derive(Clone, Copy)]
+struct AuxSend {
+    level: f32,
+    pre_fader: bool,
+}
+
+struct MixerChannel {
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    stereo: bool,
+    gain: f32,
+    pan_left: f32,
+    pan_right: f32,
+    mute: bool,
+    solo: bool,
+    sends: Vec<AuxSend>,
+}
+
+impl MixerChannel {
+    fn new(aux_count: usize) -> Self {
+        let input = SharedBuffer::default();
+        let mut channel = MixerChannel {
+            input_left: input.clone(),
+            input_right: input,
+            stereo: false,
+            gain: 1.0,
+            pan_left: 0.0,
+            pan_right: 0.0,
+            mute: false,
+            solo: false,
+            sends: vec![
+                AuxSend {
+                    level: 0.0,
+                    pre_fader: false
+                };
+                aux_count
+            ],
+        };
+        channel.set_pan(0.0);
+        channel
+    }
+
+    fn set_pan(&mut self, pan: f32) {
+        // get_pan has the left side at 1.
+        let (left, right) = get_pan(-pan.clamp(-1.0, 1.0));
+        if self.stereo {
+            // Balance: unity at center.
+            let max = left.max(right);
+            self.pan_left = left / max;
+            self.pan_right = right / max;
+        } else {
+            self.pan_left = left;
+            self.pan_right = right;
+        }
+    }
+}
+
+struct AuxBus {
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    return_left: SharedBuffer,
+    return_right: SharedBuffer,
+    return_level: f32,
+}
+
+// Channel strips summed into a stereo master, with aux send buses and returns.
+// Returns are read when the mixer is processed: processed after the mixer, an aux
+// effect is heard one chunk later.
+pub struct Mixer {
+    channels: Vec<MixerChannel>,
+    pans: Vec<f32>,
+    auxes: Vec<AuxBus>,
+    master_gain: f32,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+}
+
+impl Mixer {
+    pub fn new(channel_count: usize, aux_count: usize) -> Self {
+        let channels = (0..channel_count)
+            .map(|_| MixerChannel::new(aux_count))
+            .collect();
+        let auxes = (0..aux_count)
+            .map(|_| AuxBus {
+                output_left: SharedBuffer::default(),
+                output_right: SharedBuffer::default(),
+                return_left: SharedBuffer::default(),
+                return_right: SharedBuffer::default(),
+                return_level: 1.0,
+            })
+            .collect();
+        Mixer {
+            channels,
+            pans: vec![0.0; channel_count],
+            auxes,
+            master_gain: 1.0,
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn aux_count(&self) -> usize {
+        self.auxes.len()
+    }
+
+    pub fn set_channel_mono_input(&mut self, channel: usize, input: SharedBuffer) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            ch.input_left = input.clone();
+            ch.input_right = input;
+            ch.stereo = false;
+            ch.set_pan(self.pans[channel]);
+        }
+    }
+
+    pub fn set_channel_stereo_input(&mut self, channel: usize, left: SharedBuffer, right: SharedBuffer) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            ch.input_left = left;
+            ch.input_right = right;
+            ch.stereo = true;
+            ch.set_pan(self.pans[channel]);
+        }
+    }
+
+    pub fn set_channel_gain(&mut self, channel: usize, gain_db: f32) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            ch.gain = db_to_gain(gain_db);
+        }
+    }
+
+    // -1 (left) .. 1 (right): constant power pan for mono channels, balance for stereo ones.
+    pub fn set_channel_pan(&mut self, channel: usize, pan: f32) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            self.pans[channel] = pan.clamp(-1.0, 1.0);
+            ch.set_pan(self.pans[channel]);
+        }
+    }
+
+    pub fn set_channel_mute(&mut self, channel: usize, mute: bool) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            ch.mute = mute;
+        }
+    }
+
+    // When at least one channel is soloed, the others are muted.
+    pub fn set_channel_solo(&mut self, channel: usize, solo: bool) {
+        if let Some(ch) = self.channels.get_mut(channel) {
+            ch.solo = solo;
+        }
+    }
+
+    // Pre fader sends take the channel input before gain and pan.
+    pub fn set_channel_send(&mut self, channel: usize, aux: usize, level_db: f32, pre_fader: bool) {
+        if let Some(send) = self
+            .channels
+            .get_mut(channel)
+            .and_then(|ch| ch.sends.get_mut(aux))
+        {
+            send.level = db_to_gain(level_db);
+            send.pre_fader = pre_fader;
+        }
+    }
+
+    pub fn get_aux_left_output(&self, aux: usize) -> Option<SharedBuffer> {
+        self.auxes.get(aux).map(|a| a.output_left.clone())
+    }
+
+    pub fn get_aux_right_output(&self, aux: usize) -> Option<SharedBuffer> {
+        self.auxes.get(aux).map(|a| a.output_right.clone())
+    }
+
+    pub fn set_aux_return_inputs(&mut self, aux: usize, left: SharedBuffer, right: SharedBuffer) {
+        if let Some(a) = self.auxes.get_mut(aux) {
+            a.return_left = left;
+            a.return_right = right;
+        }
+    }
+
+    pub fn set_aux_return_level(&mut self, aux: usize, level_db: f32) {
+        if let Some(a) = self.auxes.get_mut(aux) {
+            a.return_level = db_to_gain(level_db);
+        }
+    }
+
+    pub fn set_master_gain(&mut self, gain_db: f32) {
+        self.master_gain = db_to_gain(gain_db);
+    }
+}
+
+impl Module for Mixer {
+    fn process(&mut self) {
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+        output_left.set_zero();
+        output_right.set_zero();
+
+        let solo = self.channels.iter().any(|c| c.solo);
+        let audible = |channel: &&MixerChannel| !channel.mute && (!solo || channel.solo);
+        for channel in self.channels.iter().filter(audible) {
+            let input_left = channel.input_left.try_borrow().unwrap();
+            let input_right = channel.input_right.try_borrow().unwrap();
+            let gain_left = channel.gain * channel.pan_left;
+            let gain_right = channel.gain * channel.pan_right;
+            let inputs = input_left.get().iter().zip(input_right.get());
+            let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut().iter_mut());
+            for ((out_l, out_r), (l, r)) in outputs.zip(inputs) {
+                *out_l += gain_left * l;
+                *out_r += gain_right * r;
+            }
+        }
+
+        // The buses are filled one after the other: their buffers are borrowed without allocating.
+        for (a, aux) in self.auxes.iter().enumerate() {
+            let mut aux_left = aux.output_left.try_borrow_mut().unwrap();
+            let mut aux_right = aux.output_right.try_borrow_mut().unwrap();
+            aux_left.set_zero();
+            aux_right.set_zero();
+            for channel in self.channels.iter().filter(audible) {
+                let send = channel.sends[a];
+                if send.level == 0.0 {
+                    continue;
+                }
+                let (gain_left, gain_right) = if send.pre_fader {
+                    (send.level, send.level)
+                } else {
+                    (
+                        send.level * channel.gain * channel.pan_left,
+                        send.level * channel.gain * channel.pan_right,
+                    )
+                };
+                let input_left = channel.input_left.try_borrow().unwrap();
+                let input_right = channel.input_right.try_borrow().unwrap();
+                let inputs = input_left.get().iter().zip(input_right.get());
+                let outputs = aux_left.get_mut().iter_mut().zip(aux_right.get_mut().iter_mut());
+                for ((out_l, out_r), (l, r)) in outputs.zip(inputs) {
+                    *out_l += gain_left * l;
+                    *out_r += gain_right * r;
+                }
+            }
+        }
+
+        for aux in self.auxes.iter() {
+            let return_left = aux.return_left.try_borrow().unwrap();
+            let return_right = aux.return_right.try_borrow().unwrap();
+            let returns = return_left.get().iter().zip(return_right.get());
+            let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut().iter_mut());
+            for ((out_l, out_r), (ret_l, ret_r)) in outputs.zip(returns) {
+                *out_l += aux.return_level * ret_l;
+                *out_r += aux.return_level * ret_r;
+            }
+        }
+
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut().iter_mut());
+        for (out_l, out_r) in outputs {
+            *out_l *= self.master_gain;
+            *out_r *= self.master_gain;
+        }
+    }
+}
+
+impl StereoGenerator for Mixer {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
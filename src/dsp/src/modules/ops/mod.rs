@@ -1,5 +1,7 @@
 mod adder;
+mod mixer;
 mod multiplier;
 
 pub use adder::*;
+pub use mixer::*;
 pub use multiplier::*;
@@ -1,4 +1,8 @@
 
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use crate::core::{window, SampleData, WindowType, N_SAMPLES_PER_CHUNK};
 use crate::modules::wave::{CosWave, SineWave, Wave};
 
 const COS_WAVE: CosWave = CosWave{};
@@ -14,7 +18,311 @@ pub fn get_pan(position: f32) -> (f32,f32) {
 } 
 
 
-#[derive(Debug,PartialEq)]
+// Linear interpolation between index and index + 1, silence out of the channel.
+#[inline]
+fn interpolate(channel: &[f32], index: usize, w: f32) -> f32 {
+    match (channel.get(index), channel.get(index + 1)) {
+        (Some(a), Some(b)) => a + w * (b - a),
+        (Some(a), None) => *a,
+        _ => 0.0,
+    }
+}
+
+// Adds the weighted values of one side to the mix, and the weights to its gains.
+#[inline]
+fn mix_channel(out: &mut [f32], out_gain: &mut [f32], gains: &[f32], coef: f32, values: &[f32]) {
+    let outputs = out.iter_mut().zip(out_gain.iter_mut());
+    for ((out, out_gain), (gain, value)) in outputs.zip(gains.iter().zip(values)) {
+        let gain = gain * coef;
+        *out += gain * value;
+        *out_gain += gain;
+    }
+}
+
+// Points of the window tables (plus a guard point for the interpolation).
+pub const GRAIN_WINDOW_SIZE: usize = 1024;
+
+const GAUSSIAN_SIGMA: f32 = 0.15;
+const PERCUSSIVE_ATTACK: f32 = 0.01;
+const PERCUSSIVE_DECAY: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrainWindow {
+    // Linear attack/sustain/release from the grain envelope slopes.
+    Trapezoid,
+    Hann,
+    Gaussian,
+    // Taper 0 (rectangular) .. 1 (Hann)
+    Tukey(f32),
+    Blackman,
+    // Short attack then exponential decay.
+    Percussive,
+    ReversePercussive,
+    // Trapezoid with curved attack and release: x^(2^slope), slope -4..4
+    Curved(f32),
+}
+
+impl GrainWindow {
+    // "hann", "tukey", ... the parameter is used by tukey (taper) and curved (slope).
+    pub fn from_name(name: &str, parameter: f32) -> Option<GrainWindow> {
+        match name {
+            "trapezoid" => Some(GrainWindow::Trapezoid),
+            "hann" => Some(GrainWindow::Hann),
+            "gaussian" => Some(GrainWindow::Gaussian),
+            "tukey" => Some(GrainWindow::Tukey(parameter)),
+            "blackman" => Some(GrainWindow::Blackman),
+            "percussive" => Some(GrainWindow::Percussive),
+            "reverse_percussive" => Some(GrainWindow::ReversePercussive),
+            "curved" => Some(GrainWindow::Curved(parameter)),
+            _ => None,
+        }
+    }
+}
+
+// Precomputed grain window, read with x in 0..1 over the grain life
+// (or over the trapezoid value for Curved).
+pub struct GrainWindowTable {
+    window: GrainWindow,
+    values: Vec<f32>,
+}
+
+impl GrainWindowTable {
+    pub fn new(window: GrainWindow) -> Self {
+        let window = match window {
+            GrainWindow::Tukey(taper) => GrainWindow::Tukey(taper.clamp(0.0, 1.0)),
+            GrainWindow::Curved(slope) => GrainWindow::Curved(slope.clamp(-4.0, 4.0)),
+            w => w,
+        };
+        let values = Self::compute(window);
+        GrainWindowTable { window, values }
+    }
+
+    fn compute(grain_window: GrainWindow) -> Vec<f32> {
+        let size = GRAIN_WINDOW_SIZE;
+        let x = |i: usize| i as f32 / size as f32;
+        let mut values: Vec<f32> = match grain_window {
+            GrainWindow::Trapezoid => (0..=size).map(x).collect(),
+            GrainWindow::Hann => window(WindowType::Hann, size),
+            GrainWindow::Blackman => window(WindowType::Blackman, size),
+            GrainWindow::Gaussian => {
+                let gaussian = |x: f32| (-0.5 * ((x - 0.5) / GAUSSIAN_SIGMA).powi(2)).exp();
+                // Shifted and scaled to start and end at 0.
+                let edge = gaussian(0.0);
+                (0..size).map(|i| (gaussian(x(i)) - edge) / (1.0 - edge)).collect()
+            }
+            GrainWindow::Tukey(taper) => (0..size)
+                .map(|i| {
+                    let x = x(i);
+                    let half_taper = taper / 2.0;
+                    let edge_distance = x.min(1.0 - x);
+                    if edge_distance < half_taper {
+                        0.5 - 0.5 * (PI * edge_distance / half_taper).cos()
+                    } else {
+                        1.0
+                    }
+                })
+                .collect(),
+            GrainWindow::Percussive | GrainWindow::ReversePercussive => {
+                let mut values: Vec<f32> = (0..size)
+                    .map(|i| {
+                        let x = x(i);
+                        if x < PERCUSSIVE_ATTACK {
+                            x / PERCUSSIVE_ATTACK
+                        } else {
+                            let y = (x - PERCUSSIVE_ATTACK) / (1.0 - PERCUSSIVE_ATTACK);
+                            (-PERCUSSIVE_DECAY * y).exp() * (1.0 - y)
+                        }
+                    })
+                    .collect();
+                if grain_window == GrainWindow::ReversePercussive {
+                    values.reverse();
+                    values.rotate_right(1);
+                }
+                values
+            }
+            GrainWindow::Curved(slope) => {
+                let exponent = 2.0_f32.powf(slope);
+                (0..=size).map(|i| x(i).powf(exponent)).collect()
+            }
+        };
+        if values.len() == size {
+            // Guard point: all the windows start and end at 0.
+            values.push(values[0]);
+        }
+        values
+    }
+
+    pub fn window(&self) -> GrainWindow {
+        self.window
+    }
+
+    // x: 0..1
+    #[inline]
+    pub fn lookup(&self, x: f32) -> f32 {
+        let position = x.clamp(0.0, 1.0) * GRAIN_WINDOW_SIZE as f32;
+        let i = (position as usize).min(GRAIN_WINDOW_SIZE - 1);
+        let w = position - i as f32;
+        (1.0 - w) * self.values[i] + w * self.values[i + 1]
+    }
+}
+
+impl Default for GrainWindowTable {
+    fn default() -> Self {
+        GrainWindowTable::new(GrainWindow::Trapezoid)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PitchQuantization {
+    Free,
+    Semitones,
+    Major,
+    Minor,
+    Pentatonic,
+    OctavesAndFifths,
+    Octaves,
+}
+
+impl PitchQuantization {
+    pub fn from_name(name: &str) -> Option<PitchQuantization> {
+        match name {
+            "free" => Some(PitchQuantization::Free),
+            "semitones" => Some(PitchQuantization::Semitones),
+            "major" => Some(PitchQuantization::Major),
+            "minor" => Some(PitchQuantization::Minor),
+            "pentatonic" => Some(PitchQuantization::Pentatonic),
+            "fifths" => Some(PitchQuantization::OctavesAndFifths),
+            "octaves" => Some(PitchQuantization::Octaves),
+            _ => None,
+        }
+    }
+
+    // Allowed semitones in an octave.
+    fn degrees(&self) -> &'static [i32] {
+        match self {
+            PitchQuantization::Free | PitchQuantization::Semitones => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            PitchQuantization::Major => &[0, 2, 4, 5, 7, 9, 11],
+            PitchQuantization::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            PitchQuantization::Pentatonic => &[0, 2, 4, 7, 9],
+            PitchQuantization::OctavesAndFifths => &[0, 7],
+            PitchQuantization::Octaves => &[0],
+        }
+    }
+
+    // Closest allowed offset, in semitones.
+    pub fn quantize(&self, semi_tones: f32) -> f32 {
+        if *self == PitchQuantization::Free {
+            return semi_tones;
+        }
+        let octave = (semi_tones / 12.0).floor() as i32;
+        let mut closest = semi_tones;
+        let mut distance = f32::MAX;
+        // Candidates in this octave and the next one (the closest degree may be above).
+        for o in [octave, octave + 1] {
+            for degree in self.degrees() {
+                let candidate = (12 * o + degree) as f32;
+                if (candidate - semi_tones).abs() < distance {
+                    distance = (candidate - semi_tones).abs();
+                    closest = candidate;
+                }
+            }
+        }
+        closest
+    }
+}
+
+// Behaviour of the grains at the edges of the sample region.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionEdge {
+    // Held on the edge.
+    Clamp,
+    // Continues from the other edge.
+    Wrap,
+    // Bounces back, the grain changes direction.
+    Mirror,
+}
+
+impl RegionEdge {
+    pub fn from_name(name: &str) -> Option<RegionEdge> {
+        match name {
+            "clamp" => Some(RegionEdge::Clamp),
+            "wrap" => Some(RegionEdge::Wrap),
+            "mirror" => Some(RegionEdge::Mirror),
+            _ => None,
+        }
+    }
+}
+
+// Part of the sample the grains read: start..end (both included).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrainRegion {
+    pub start: f32,
+    pub end: f32,
+    pub edge: RegionEdge,
+}
+
+impl GrainRegion {
+    pub fn length(&self) -> f32 {
+        self.end - self.start
+    }
+
+    // Position moved back into the region, direction reversed when mirrored.
+    #[inline]
+    fn constrain(&self, position: f32, direction: &mut f32) -> f32 {
+        if position >= self.start && position <= self.end {
+            return position;
+        }
+        let length = self.length();
+        if length <= 0.0 {
+            return self.start;
+        }
+        match self.edge {
+            RegionEdge::Clamp => position.clamp(self.start, self.end),
+            RegionEdge::Wrap => self.start + (position - self.start).rem_euclid(length),
+            RegionEdge::Mirror => {
+                // Triangle over two lengths, an odd number of bounces reverses the grain.
+                let bounces = ((position - self.start) / length).floor() as i64;
+                if bounces.rem_euclid(2) == 1 {
+                    *direction = -*direction;
+                }
+                let offset = (position - self.start).rem_euclid(2.0 * length);
+                if offset > length {
+                    self.start + 2.0 * length - offset
+                } else {
+                    self.start + offset
+                }
+            }
+        }
+    }
+}
+
+// Random variations drawn by each new grain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrainVariation {
+    // Semitones, the pitch offset is drawn in -pitch..pitch
+    pub pitch: f32,
+    pub quantization: PitchQuantization,
+    // 0..1, random attenuation
+    pub gain: f32,
+    // 0..1, duration scaled by 1 +/- duration
+    pub duration: f32,
+    // 0..1, probability of playing the grain backward
+    pub reverse: f32,
+}
+
+impl Default for GrainVariation {
+    fn default() -> Self {
+        GrainVariation {
+            pitch: 0.0,
+            quantization: PitchQuantization::Free,
+            gain: 0.0,
+            duration: 0.0,
+            reverse: 0.0,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
 pub enum GrainState {
     Attack,
     Sustain,
@@ -23,6 +331,7 @@ pub enum GrainState {
 }
 
 
+#[derive(Clone)]
 pub struct Grain {
     position: f32,
     state: GrainState,
@@ -32,12 +341,66 @@ pub struct Grain {
     sustain_time: f32,
     release_slope: f32,
     value: f32,
+    // Progress through the window, 0..1
+    phase: f32,
+    phase_step: f32,
+    // Pitch ratio of the grain, negative when playing backward.
+    step_ratio: f32,
+    // Reads the left channel of the sample on the right and vice versa.
+    swap: bool,
+    // Creation order, to find the oldest grain.
+    birth: u64,
+    // Last envelope gain, to find the quietest grain.
+    level: f32,
+    // The envelope has not reached its peak yet.
+    rising: bool,
+    // Stolen grains fade out quickly, 1 otherwise.
+    fade_out: f32,
+    fade_step: f32,
+    stolen: bool,
 }
 
-pub struct GrainResult {
-    pub position: f32,
-    pub l_value: f32,
-    pub r_value: f32,
+// Grains summed over a chunk, with the sum of their envelopes for the normalization.
+pub struct GrainMix {
+    left: [f32; N_SAMPLES_PER_CHUNK],
+    right: [f32; N_SAMPLES_PER_CHUNK],
+    l_gain: [f32; N_SAMPLES_PER_CHUNK],
+    r_gain: [f32; N_SAMPLES_PER_CHUNK],
+}
+
+impl GrainMix {
+    pub fn new() -> Self {
+        GrainMix {
+            left: [0.0; N_SAMPLES_PER_CHUNK],
+            right: [0.0; N_SAMPLES_PER_CHUNK],
+            l_gain: [0.0; N_SAMPLES_PER_CHUNK],
+            r_gain: [0.0; N_SAMPLES_PER_CHUNK],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = GrainMix::new();
+    }
+
+    // (left, right), divided by the sum of the gains when it is over 1.
+    #[inline]
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        let mut left = self.left[index];
+        let mut right = self.right[index];
+        if self.l_gain[index] > 1.0 {
+            left /= self.l_gain[index];
+        }
+        if self.r_gain[index] > 1.0 {
+            right /= self.r_gain[index];
+        }
+        (left, right)
+    }
+}
+
+impl Default for GrainMix {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Grain {
@@ -50,6 +413,16 @@ impl Grain {
         let sustain_time = 0.0;
         let release_slope = 0.0;
         let value = 0.0;
+        let phase = 0.0;
+        let phase_step = 0.0;
+        let step_ratio = 1.0;
+        let swap = false;
+        let birth = 0;
+        let level = 0.0;
+        let rising = true;
+        let fade_out = 1.0;
+        let fade_step = 0.0;
+        let stolen = false;
         Grain {
             position,
             state,
@@ -59,52 +432,187 @@ impl Grain {
             sustain_time,
             release_slope,
             value,
+            phase,
+            phase_step,
+            step_ratio,
+            swap,
+            birth,
+            level,
+            rising,
+            fade_out,
+            fade_step,
+            stolen,
         }
     }
 
-    #[inline]
-    pub fn next(&mut self, step: f32, start: f32, end: f32) -> Option<GrainResult> {
-        let opt_value = match self.state {
-            GrainState::Attack => {
-                self.value += self.attack_slope;
-                if self.value >= 1.0 {
-                    self.value = 1.0;
-                    self.state = GrainState::Sustain;
-                };
-                Some(self.value)
-            },
-            GrainState::Sustain => {
-                self.sustain_time -= 1.0;
-                if self.sustain_time <= 0.0 {
-                    self.state = GrainState::Release;
-                }
-                Some(1.0)
-            },
-            GrainState::Release => {
-                self.value += self.release_slope;
-                if self.value < 0.0 {
-                    self.value = 0.0;
-                    self.state = GrainState::Stopped;
-                };
-                Some(self.value)
-            }
-            GrainState::Stopped => None,
+    // Linear attack/sustain/release over the frames, returns the number of frames before the grain stops.
+    fn trapezoid(&mut self, gains: &mut [f32]) -> usize {
+        let mut i = 0;
+        while i < gains.len() {
+            match self.state {
+                GrainState::Attack => {
+                    let mut value = self.value;
+                    while i < gains.len() && value < 1.0 {
+                        value = (value + self.attack_slope).min(1.0);
+                        gains[i] = value;
+                        i += 1;
+                    }
+                    self.value = value;
+                    if value >= 1.0 {
+                        self.state = GrainState::Sustain;
+                    }
+                },
+                GrainState::Sustain => {
+                    // The sustain time is counted down after each frame: at least one frame.
+                    let remaining = self.sustain_time.ceil().max(1.0) as usize;
+                    let count = remaining.min(gains.len() - i);
+                    gains[i..i + count].fill(1.0);
+                    i += count;
+                    self.sustain_time -= count as f32;
+                    if count == remaining {
+                        self.state = GrainState::Release;
+                    }
+                },
+                GrainState::Release => {
+                    let mut value = self.value;
+                    while i < gains.len() {
+                        value += self.release_slope;
+                        if value < 0.0 {
+                            value = 0.0;
+                            self.state = GrainState::Stopped;
+                        }
+                        gains[i] = value;
+                        i += 1;
+                        if self.state == GrainState::Stopped {
+                            break;
+                        }
+                    }
+                    self.value = value;
+                },
+                GrainState::Stopped => return i,
+            }
+        }
+        i
+    }
+
+    // Reads the window table with the progress of the grain.
+    fn windowed(&mut self, window: &GrainWindowTable, gains: &mut [f32]) -> usize {
+        let mut phase = self.phase;
+        let mut i = 0;
+        while i < gains.len() && self.state != GrainState::Stopped {
+            gains[i] = window.lookup(phase);
+            phase += self.phase_step;
+            if phase >= 1.0 {
+                self.state = GrainState::Stopped;
+            }
+            i += 1;
+        }
+        self.phase = phase;
+        i
+    }
+
+    // Envelope over the frames (with the fade out of the stolen grains),
+    // returns the number of frames before the grain stops.
+    fn envelope(&mut self, window: &GrainWindowTable, gains: &mut [f32]) -> usize {
+        let mut count = match window.window() {
+            GrainWindow::Trapezoid => self.trapezoid(gains),
+            GrainWindow::Curved(_) => {
+                let count = self.trapezoid(gains);
+                gains[..count].iter_mut().for_each(|v| *v = window.lookup(*v));
+                count
+            }
+            _ => self.windowed(window, gains),
         };
-        
-        opt_value.map(|value| {
-            let new_position = self.position + step;
-            // Todo change pos...
-            self.position = match new_position {
-                p if p >= end => start,
-                p if p < start => end - 1.0,
-                p => p
-            };
-            GrainResult {
-                position: self.position, 
-                l_value: value * self.l_coef,
-                r_value: value * self.r_coef,
+        if self.stolen {
+            for (i, value) in gains[..count].iter_mut().enumerate() {
+                self.fade_out -= self.fade_step;
+                if self.fade_out <= 0.0 {
+                    self.stop();
+                    count = i;
+                    break;
+                }
+                *value *= self.fade_out;
             }
-        })
+        }
+        count
+    }
+
+    // Adds the grain to the mix over the frames, false once it has stopped.
+    pub fn render(&mut self, frames: Range<usize>, step: f32, region: &GrainRegion, window: &GrainWindowTable, samples: &SampleData, mix: &mut GrainMix) -> bool {
+        let mut gains = [0.0; N_SAMPLES_PER_CHUNK];
+        let gains = &mut gains[..frames.len()];
+        let count = self.envelope(window, gains);
+        if count > 0 {
+            let level = gains[count - 1] * (self.l_coef + self.r_coef) / 2.0;
+            self.rising = level >= self.level;
+            self.level = level;
+        }
+
+        let (left, right) = samples.stereo_channels();
+        let (left, right) = if self.swap { (right, left) } else { (left, right) };
+        let mut left_values = [0.0; N_SAMPLES_PER_CHUNK];
+        let mut right_values = [0.0; N_SAMPLES_PER_CHUNK];
+        let left_values = &mut left_values[..count];
+        let right_values = &mut right_values[..count];
+
+        let increment = step * self.step_ratio;
+        let first = self.position + 1.0;
+        let last = self.position + count as f32;
+        let contiguous = increment == 1.0
+            && first >= region.start
+            && last <= region.end
+            && (last as usize) + 1 < left.len().min(right.len());
+        if contiguous {
+            // Same interpolation weight on all the frames, the reads follow each other.
+            let index = first as usize;
+            let w = first - index as f32;
+            for (channel, values) in [(left, &mut *left_values), (right, &mut *right_values)] {
+                let a = &channel[index..index + count];
+                let b = &channel[index + 1..index + count + 1];
+                for ((v, a), b) in values.iter_mut().zip(a).zip(b) {
+                    *v = a + w * (b - a);
+                }
+            }
+            self.position = last;
+        } else {
+            let mut position = self.position;
+            let mut step_ratio = self.step_ratio;
+            for (l, r) in left_values.iter_mut().zip(right_values.iter_mut()) {
+                position += step * step_ratio;
+                if position < region.start || position > region.end {
+                    position = region.constrain(position, &mut step_ratio);
+                }
+                let index = position as usize;
+                let w = position - index as f32;
+                *l = interpolate(left, index, w);
+                *r = interpolate(right, index, w);
+            }
+            self.position = position;
+            self.step_ratio = step_ratio;
+        }
+
+        // Mixed apart from the reads so that the loops vectorize.
+        let mix_frames = frames.start..frames.start + count;
+        let gains = &gains[..count];
+        let (l_coef, r_coef) = (self.l_coef, self.r_coef);
+        let (out, out_gain) = (&mut mix.left[mix_frames.clone()], &mut mix.l_gain[mix_frames.clone()]);
+        mix_channel(out, out_gain, gains, l_coef, left_values);
+        let (out, out_gain) = (&mut mix.right[mix_frames.clone()], &mut mix.r_gain[mix_frames]);
+        mix_channel(out, out_gain, gains, r_coef, right_values);
+        count == frames.len() && self.is_active()
+    }
+
+    // Frames rendered before the grain stops when it stops within the frames, the grain is not changed.
+    fn stop_frame(&self, window: &GrainWindowTable, frames: usize) -> Option<usize> {
+        let trapezoid = matches!(window.window(), GrainWindow::Trapezoid | GrainWindow::Curved(_));
+        if trapezoid && !self.stolen && self.state == GrainState::Sustain && self.sustain_time >= frames as f32 {
+            // Sustained over all the frames.
+            return None;
+        }
+        let mut probe = self.clone();
+        let mut gains = [0.0; N_SAMPLES_PER_CHUNK];
+        let count = probe.envelope(window, &mut gains[..frames]);
+        if probe.is_active() { None } else { Some(count) }
     }
 
     pub fn recycle(&mut self,position: f32, pan_spread: f32, location_spread: f32, attack_slope: f32, sustain_time: f32, release_slope: f32){
@@ -121,6 +629,74 @@ impl Grain {
         self.attack_slope = attack_slope;
         self.sustain_time = sustain_time;
         self.release_slope = release_slope;
+        // Same duration as the trapezoid for the other windows.
+        let duration = 1.0 / attack_slope + sustain_time - 1.0 / release_slope;
+        self.phase = 0.0;
+        self.phase_step = 1.0 / duration.max(1.0);
+        self.step_ratio = 1.0;
+        self.swap = false;
+        self.level = 0.0;
+        self.rising = true;
+        self.fade_out = 1.0;
+        self.stolen = false;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state != GrainState::Stopped
+    }
+
+    // Last gain, or the expected peak while the envelope rises (new grains are not the quietest).
+    pub fn loudness(&self) -> f32 {
+        if self.rising {
+            (self.l_coef + self.r_coef) / 2.0
+        } else {
+            self.level
+        }
+    }
+
+    // Playing and not fading out after being stolen.
+    pub fn is_sounding(&self) -> bool {
+        self.is_active() && !self.stolen
+    }
+
+    // Starts a short fade out, fade_step: decrease of the gain per sample.
+    pub fn steal(&mut self, fade_step: f32) {
+        self.stolen = true;
+        self.fade_step = fade_step;
+    }
+
+    // The stolen flag is kept: Grains counts the fading grains when they are released.
+    fn stop(&mut self) {
+        self.state = GrainState::Stopped;
+        self.level = 0.0;
+    }
+
+    pub fn randomize_playback(&mut self, variation: &GrainVariation) {
+        let pitch = variation.pitch * (2.0 * fastrand::f32() - 1.0);
+        let pitch = variation.quantization.quantize(pitch);
+        self.step_ratio = 2.0_f32.powf(pitch / 12.0);
+        if fastrand::f32() < variation.reverse {
+            self.step_ratio = -self.step_ratio;
+        }
+
+        let gain = 1.0 - variation.gain * fastrand::f32();
+        self.l_coef *= gain;
+        self.r_coef *= gain;
+
+        let duration = 1.0 + variation.duration * (2.0 * fastrand::f32() - 1.0);
+        let duration = duration.max(0.05);
+        self.attack_slope /= duration;
+        self.sustain_time *= duration;
+        self.release_slope /= duration;
+        self.phase_step /= duration;
+    }
+
+    // swap_probability: 0..1, balance_spread: 0..1 (random balance between the sides)
+    pub fn randomize_channels(&mut self, swap_probability: f32, balance_spread: f32) {
+        self.swap = fastrand::f32() < swap_probability;
+        let balance = balance_spread * (2.0 * fastrand::f32() - 1.0);
+        self.l_coef *= (1.0 - balance).min(1.0);
+        self.r_coef *= (1.0 + balance).min(1.0);
     }
 }
 
@@ -130,13 +706,85 @@ impl Default for Grain {
     }
 }
 
-pub const MAX_GRAINS: usize = 32;
+// Preallocated grains, the pool size can be set up to this.
+pub const MAX_GRAINS: usize = 512;
+pub const DEFAULT_GRAIN_POOL_SIZE: usize = 32;
+
+// Largest number of grains started per burst.
+pub const MAX_BURST_SIZE: usize = 64;
+
+// Fade out of the stolen grains.
+const STEAL_FADE_MS: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StealingPolicy {
+    // New grains wait for a free grain.
+    None,
+    Oldest,
+    // Lowest gain, the grains before their peak count at their peak.
+    Quietest,
+}
+
+impl StealingPolicy {
+    pub fn from_name(name: &str) -> Option<StealingPolicy> {
+        match name {
+            "none" => Some(StealingPolicy::None),
+            "oldest" => Some(StealingPolicy::Oldest),
+            "quietest" => Some(StealingPolicy::Quietest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrainScheduling {
+    // Regular intervals, randomized by the jitter.
+    Synchronous,
+    // Poisson process: exponentially distributed intervals with the same mean density.
+    Asynchronous,
+    // N grains at once on each (jittered) trigger.
+    Burst(usize),
+}
+
+impl GrainScheduling {
+    // "synchronous", "asynchronous" or "burst" (with its size).
+    pub fn from_name(name: &str, burst_size: usize) -> Option<GrainScheduling> {
+        match name {
+            "synchronous" => Some(GrainScheduling::Synchronous),
+            "asynchronous" => Some(GrainScheduling::Asynchronous),
+            "burst" => Some(GrainScheduling::Burst(burst_size.clamp(1, MAX_BURST_SIZE))),
+            _ => None,
+        }
+    }
+}
 
 pub struct Grains {
     // Orchestration
     time_beetween_grains: f32,
     current_time: f32,
-    pub grains: Vec<Grain>,
+    // Time of the next trigger, drawn from the scheduling mode.
+    next_trigger: f32,
+    scheduling: GrainScheduling,
+    jitter: f32,
+    // Grains of the current trigger still waiting for a free slot.
+    pending_grains: usize,
+    // The pending grains found the pool full: they wait for a grain to stop.
+    blocked: bool,
+    grains: Vec<Grain>,
+    // Indices of the playing grains and of the stopped ones, preallocated:
+    // only the playing grains are processed and no allocation happens in the audio loop.
+    active: Vec<usize>,
+    free: Vec<usize>,
+    // Stolen grains still fading out.
+    fading: usize,
+    // Maximum number of sounding grains (stolen grains fading out are not counted).
+    pool_size: usize,
+    stealing_policy: StealingPolicy,
+    steal_fade_step: f32,
+    grain_counter: u64,
+    channel_swap: f32,
+    balance_spread: f32,
+    variation: GrainVariation,
 }
 
 impl Grains {
@@ -146,42 +794,333 @@ impl Grains {
         for _ in 0..MAX_GRAINS {
             grains.push(Grain::default());
         }
+        let active = Vec::with_capacity(MAX_GRAINS);
+        // Reversed: the first grains are used first.
+        let free = (0..MAX_GRAINS).rev().collect();
         Self {
             time_beetween_grains,
             current_time,
+            next_trigger: time_beetween_grains,
+            scheduling: GrainScheduling::Synchronous,
+            jitter: 0.0,
+            pending_grains: 0,
+            blocked: false,
             grains,
+            active,
+            free,
+            fading: 0,
+            pool_size: DEFAULT_GRAIN_POOL_SIZE,
+            stealing_policy: StealingPolicy::None,
+            steal_fade_step: 1000.0 / (STEAL_FADE_MS * 44_100.0),
+            grain_counter: 0,
+            channel_swap: 0.0,
+            balance_spread: 0.0,
+            variation: GrainVariation::default(),
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32){
+        self.steal_fade_step = 1000.0 / (STEAL_FADE_MS * sample_rate);
+    }
+
     pub fn set_grain_density(&mut self, time_beetween_grains: f32){
         self.time_beetween_grains = time_beetween_grains;
+        self.next_trigger = self.draw_interval();
+    }
+
+    pub fn set_scheduling(&mut self, scheduling: GrainScheduling){
+        self.scheduling = scheduling;
+        self.next_trigger = self.draw_interval();
+    }
+
+    // Random deviation of the synchronous and burst intervals, 0..1 (of the interval)
+    pub fn set_jitter(&mut self, jitter: f32){
+        self.jitter = jitter.clamp(0.0, 1.0);
+    }
+
+    fn draw_interval(&self) -> f32 {
+        match self.scheduling {
+            GrainScheduling::Asynchronous => {
+                // 1 - r is in 0..1 (excluded): the log is finite.
+                -(1.0 - fastrand::f32()).ln() * self.time_beetween_grains
+            }
+            GrainScheduling::Synchronous | GrainScheduling::Burst(_) => {
+                self.time_beetween_grains * (1.0 + self.jitter * (2.0 * fastrand::f32() - 1.0))
+            }
+        }
+    }
+
+    // 1..MAX_GRAINS, the grains over the new size fade out.
+    pub fn set_pool_size(&mut self, pool_size: usize){
+        self.pool_size = pool_size.clamp(1, MAX_GRAINS);
+        self.blocked = false;
+        let mut excess = self.sounding_count().saturating_sub(self.pool_size);
+        while excess > 0 {
+            match self.victim(StealingPolicy::Oldest) {
+                Some(i) => self.steal(i),
+                None => break,
+            }
+            excess -= 1;
+        }
+    }
+
+    pub fn pool_size(&self) -> usize {
+        self.pool_size
+    }
+
+    pub fn set_stealing_policy(&mut self, policy: StealingPolicy){
+        self.stealing_policy = policy;
+        self.blocked = false;
+    }
+
+    // Playing grains, including the ones fading out.
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    fn sounding_count(&self) -> usize {
+        self.active.len() - self.fading
+    }
+
+    // Sounding grain to steal.
+    fn victim(&self, policy: StealingPolicy) -> Option<usize> {
+        let grains = &self.grains;
+        let sounding = self.active.iter().copied().filter(|&i| grains[i].is_sounding());
+        match policy {
+            StealingPolicy::None => None,
+            StealingPolicy::Oldest => sounding.min_by_key(|&i| grains[i].birth),
+            StealingPolicy::Quietest => sounding
+                .min_by(|&a, &b| grains[a].loudness().total_cmp(&grains[b].loudness())),
+        }
+    }
+
+    fn steal(&mut self, index: usize) {
+        self.grains[index].steal(self.steal_fade_step);
+        self.fading += 1;
+    }
+
+    // Probability for a new grain to swap the channels of the sample, 0..1
+    pub fn set_channel_swap(&mut self, probability: f32){
+        self.channel_swap = probability.clamp(0.0, 1.0);
+    }
+
+    // Random balance of the new grains, 0..1
+    pub fn set_balance_spread(&mut self, spread: f32){
+        self.balance_spread = spread.clamp(0.0, 1.0);
+    }
+
+    // semi_tones: spread of the pitch offset of the new grains
+    pub fn set_pitch_spread(&mut self, semi_tones: f32, quantization: PitchQuantization){
+        self.variation.pitch = semi_tones.abs();
+        self.variation.quantization = quantization;
+    }
+
+    // Random attenuation of the new grains, 0..1
+    pub fn set_gain_spread(&mut self, spread: f32){
+        self.variation.gain = spread.clamp(0.0, 1.0);
+    }
+
+    // Random duration of the new grains, 0..1
+    pub fn set_duration_spread(&mut self, spread: f32){
+        self.variation.duration = spread.clamp(0.0, 1.0);
+    }
+
+    // Probability for a new grain to play backward, 0..1
+    pub fn set_reverse_probability(&mut self, probability: f32){
+        self.variation.reverse = probability.clamp(0.0, 1.0);
+    }
+
+    // Advances the scheduler by one sample, true when grains have to be started.
+    #[inline]
+    pub fn tick(&mut self, step: f32) -> bool {
+        self.current_time += step;
+        if self.pending_grains == 0 && self.current_time >= self.next_trigger {
+            // Keep the remainder: the triggers do not drift.
+            self.current_time = (self.current_time - self.next_trigger).max(0.0);
+            self.next_trigger = self.draw_interval();
+            self.pending_grains = match self.scheduling {
+                GrainScheduling::Burst(size) => size,
+                _ => 1,
+            };
+        }
+        self.pending_grains > 0 && !self.blocked
     }
 
     // pan_spread 0 .. 1
     // location_spread: length of the sample/2
-    pub fn grain_scheduler(&mut self, step: f32, scanner_location: f32, pan_spread: f32, location_spread: f32,attack_slope: f32, sustain_time: f32, release_slope: f32){
-        self.current_time += step;
-        if self.current_time > self.time_beetween_grains {
-            // Create new grain if possible, if not we have to wait the next step...
-            if self.create_grain(scanner_location, pan_spread, location_spread, attack_slope, sustain_time, release_slope){
-                self.current_time = 0.0;
-            } else {
-                self.current_time = self.time_beetween_grains;
+    pub fn start_pending(&mut self, scanner_location: f32, pan_spread: f32, location_spread: f32,attack_slope: f32, sustain_time: f32, release_slope: f32){
+        // Create new grains if possible, if not they wait for a grain to stop (see free_frame).
+        while self.pending_grains > 0 {
+            if !self.create_grain(scanner_location, pan_spread, location_spread, attack_slope, sustain_time, release_slope){
+                self.blocked = true;
+                break;
             }
+            self.pending_grains -= 1;
         }
     }
 
     pub fn create_grain(&mut self, scanner_location: f32, pan_spread: f32, location_spread: f32, attack_slope: f32, sustain_time: f32, release_slope: f32) -> bool {
-        let opt_grain = self.grains
-            .iter_mut()
-            .filter(|g| g.state == GrainState::Stopped)
-            .nth(0);
-        if let Some(g) = opt_grain {
+        if self.sounding_count() >= self.pool_size {
+            match self.victim(self.stealing_policy) {
+                Some(i) => self.steal(i),
+                None => return false,
+            }
+        }
+        if let Some(i) = self.free.pop() {
+            self.active.push(i);
+            self.grain_counter += 1;
+            let g = &mut self.grains[i];
+            g.birth = self.grain_counter;
             g.recycle(scanner_location, pan_spread, location_spread, attack_slope, sustain_time, release_slope);
+            g.randomize_channels(self.channel_swap, self.balance_spread);
+            g.randomize_playback(&self.variation);
             true
         } else {
             false
         }
     }
+
+    // First frame where a grain frees its slot for the grains blocked by a full pool,
+    // None when no grain is blocked or no slot frees before the end of the frames.
+    pub fn free_frame(&self, frames: Range<usize>, window: &GrainWindowTable) -> Option<usize> {
+        if !self.blocked {
+            return None;
+        }
+        let mut first = frames.len();
+        for &i in self.active.iter() {
+            if let Some(count) = self.grains[i].stop_frame(window, first) {
+                first = count;
+            }
+        }
+        (first < frames.len()).then_some(frames.start + first)
+    }
+
+    // Adds the playing grains to the mix over the frames (a part of the chunk without new grains).
+    // Each grain is processed over all the frames at once, the stopped ones go back to the free list.
+    pub fn render(&mut self, frames: Range<usize>, step: f32, region: &GrainRegion, window: &GrainWindowTable, samples: &SampleData, mix: &mut GrainMix) {
+        let mut a = 0;
+        while a < self.active.len() {
+            let i = self.active[a];
+            let grain = &mut self.grains[i];
+            if grain.render(frames.clone(), step, region, window, samples, mix) {
+                a += 1;
+            } else {
+                if grain.stolen {
+                    self.fading -= 1;
+                }
+                grain.stop();
+                self.active.swap_remove(a);
+                self.free.push(i);
+                self.blocked = false;
+            }
+        }
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTH: usize = 4096;
+    // Chunks covering the whole grain: 100 + 1000 + 100 frames.
+    const CHUNKS: usize = 40;
+
+    fn samples() -> SampleData {
+        let left = (0..LENGTH).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right = (0..LENGTH).map(|i| (i as f32 * 0.031).cos()).collect();
+        SampleData::new(vec![left, right])
+    }
+
+    fn grain(position: f32, step_ratio: f32) -> Grain {
+        let mut grain = Grain::new();
+        grain.recycle(position, 0.0, 0.0, 0.01, 1000.0, -0.01);
+        grain.step_ratio = step_ratio;
+        grain
+    }
+
+    // The engine before the block rendering: envelope, position and reads one frame at a time.
+    fn render_per_sample(grain: &mut Grain, step: f32, region: &GrainRegion, window: &GrainWindowTable, samples: &SampleData, mix: &mut GrainMix) {
+        let (left, right) = samples.stereo_channels();
+        for i in 0..N_SAMPLES_PER_CHUNK {
+            let mut gain = [0.0];
+            if grain.envelope(window, &mut gain) == 0 {
+                return;
+            }
+            grain.position += step * grain.step_ratio;
+            grain.position = region.constrain(grain.position, &mut grain.step_ratio);
+            let index = grain.position as usize;
+            let w = grain.position - index as f32;
+            let (l_value, r_value) = (gain[0] * grain.l_coef, gain[0] * grain.r_coef);
+            mix.left[i] += l_value * interpolate(left, index, w);
+            mix.right[i] += r_value * interpolate(right, index, w);
+            mix.l_gain[i] += l_value;
+            mix.r_gain[i] += r_value;
+        }
+    }
+
+    fn assert_same_output(position: f32, step: f32, step_ratio: f32, region: GrainRegion, window: GrainWindow) {
+        let samples = samples();
+        let window = GrainWindowTable::new(window);
+        let mut block = grain(position, step_ratio);
+        let mut per_sample = grain(position, step_ratio);
+        let mut block_mix = GrainMix::new();
+        let mut per_sample_mix = GrainMix::new();
+        for chunk in 0..CHUNKS {
+            block_mix.clear();
+            per_sample_mix.clear();
+            block.render(0..N_SAMPLES_PER_CHUNK, step, &region, &window, &samples, &mut block_mix);
+            render_per_sample(&mut per_sample, step, &region, &window, &samples, &mut per_sample_mix);
+            for i in 0..N_SAMPLES_PER_CHUNK {
+                let (l, r) = block_mix.frame(i);
+                let (l_ref, r_ref) = per_sample_mix.frame(i);
+                assert!(
+                    (l - l_ref).abs() < 1e-5 && (r - r_ref).abs() < 1e-5,
+                    "chunk {} frame {}: ({}, {}) != ({}, {})", chunk, i, l, r, l_ref, r_ref
+                );
+            }
+            assert_eq!(block.is_active(), per_sample.is_active());
+        }
+        assert!(!block.is_active());
+    }
+
+    fn region(start: f32, end: f32, edge: RegionEdge) -> GrainRegion {
+        GrainRegion { start, end, edge }
+    }
+
+    #[test]
+    fn unit_step_matches_per_sample() {
+        let whole = region(0.0, LENGTH as f32 - 1.0, RegionEdge::Wrap);
+        assert_same_output(100.25, 1.0, 1.0, whole, GrainWindow::Trapezoid);
+        // Wraps at the end of the sample.
+        assert_same_output(3000.5, 1.0, 1.0, whole, GrainWindow::Hann);
+    }
+
+    #[test]
+    fn pitched_step_matches_per_sample() {
+        let whole = region(0.0, LENGTH as f32 - 1.0, RegionEdge::Wrap);
+        assert_same_output(100.25, 1.0, 1.37, whole, GrainWindow::Trapezoid);
+        assert_same_output(100.25, 0.5, 1.0, whole, GrainWindow::Gaussian);
+        assert_same_output(2000.0, 1.5, 1.26, whole, GrainWindow::Curved(2.0));
+    }
+
+    #[test]
+    fn reverse_matches_per_sample() {
+        let whole = region(0.0, LENGTH as f32 - 1.0, RegionEdge::Wrap);
+        // Wraps below the start of the sample.
+        assert_same_output(500.75, 1.0, -1.0, whole, GrainWindow::Trapezoid);
+        assert_same_output(500.75, 1.0, -0.8, whole, GrainWindow::Hann);
+    }
+
+    #[test]
+    fn region_edges_match_per_sample() {
+        // Shorter than the grain: several bounces or wraps.
+        for edge in [RegionEdge::Mirror, RegionEdge::Wrap, RegionEdge::Clamp] {
+            let small = region(1000.0, 1400.0, edge);
+            assert_same_output(1390.5, 1.0, 1.0, small, GrainWindow::Trapezoid);
+            assert_same_output(1010.5, 1.0, -1.0, small, GrainWindow::Trapezoid);
+            assert_same_output(1200.0, 1.0, 1.5, small, GrainWindow::Hann);
+            assert_same_output(1200.0, 2.0, -0.7, small, GrainWindow::Trapezoid);
+        }
+    }
+}
@@ -1,11 +1,50 @@
 use std::{cell::RefCell, f32, rc::Rc};
 
-use crate::core::{Buffer, Module, MonoGenerator, SharedBuffer, StereoGenerator};
+use crate::core::{
+    find_zero_crossing, Buffer, CrossfadeCurve, LoopCrossfade, Module, MonoGenerator, SampleData,
+    SharedBuffer, StereoGenerator, MAX_ZERO_CROSSING_DISTANCE, N_SAMPLES_PER_CHUNK,
+};
 
-use super::{GrainResult, Grains, MAX_GRAINS};
+use super::{
+    GrainMix, GrainRegion, GrainScheduling, GrainWindow, GrainWindowTable, Grains, PitchQuantization,
+    RegionEdge, StealingPolicy,
+};
+
+// Time for the scan head to reach a new speed or position, in seconds.
+const SCAN_GLIDE_TIME: f32 = 0.05;
+
+// Movement of the scan head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanMode {
+    Forward,
+    Reverse,
+    // Position held.
+    Freeze,
+    // Random moves of up to the given size (samples) at each sample.
+    RandomWalk(f32),
+    // Glides to a random position every given number of seconds.
+    Jump(f32),
+    // Glides to the position set with set_scan_position.
+    Manual,
+}
+
+impl ScanMode {
+    // "forward", "reverse", "freeze", "random_walk" (size), "jump" (interval), "manual".
+    pub fn from_name(name: &str, parameter: f32) -> Option<ScanMode> {
+        match name {
+            "forward" => Some(ScanMode::Forward),
+            "reverse" => Some(ScanMode::Reverse),
+            "freeze" => Some(ScanMode::Freeze),
+            "random_walk" => Some(ScanMode::RandomWalk(parameter)),
+            "jump" => Some(ScanMode::Jump(parameter)),
+            "manual" => Some(ScanMode::Manual),
+            _ => None,
+        }
+    }
+}
 
 pub struct Granulator {
-    samples: Vec<f32>,
+    samples: SampleData,
     output_left: SharedBuffer,
     output_right: SharedBuffer,
     index: f32,
@@ -21,7 +60,17 @@ pub struct Granulator {
     grain_attack_slope: f32,
     grain_sustain_duration: f32,
     grain_release_slope: f32,
-    vec_result: Vec<GrainResult>,
+    grain_window: GrainWindowTable,
+    mix: GrainMix,
+    crossfade: LoopCrossfade,
+    zero_crossing_snap: bool,
+    region_edge: RegionEdge,
+    scan_mode: ScanMode,
+    // Current speed of the scan head, glides to the speed of the mode.
+    scan_velocity: f32,
+    // Target of the Jump and Manual modes.
+    scan_target: f32,
+    jump_countdown: f32,
 }
 
 impl Granulator {
@@ -32,7 +81,7 @@ impl Granulator {
         let output_left = Rc::new(RefCell::new(Buffer::new()));
         let output_right = Rc::new(RefCell::new(Buffer::new()));
 
-        let samples = vec![0.0, 0.0, 0.0, 0.0, 0.0];
+        let samples = SampleData::from_mono(vec![0.0, 0.0, 0.0, 0.0, 0.0]);
         let level = 1.0;
         let start = 0;
         let end = samples.len();
@@ -46,7 +95,6 @@ impl Granulator {
         let grain_sustain_duration = 1_000.0;
         let grain_release_slope= -0.1;
 
-        let vec_result  = Vec::with_capacity(MAX_GRAINS);
         Self {
             index,
             step,
@@ -64,15 +112,26 @@ impl Granulator {
             grain_attack_slope,
             grain_sustain_duration,
             grain_release_slope,
-            vec_result,
+            grain_window: GrainWindowTable::default(),
+            mix: GrainMix::new(),
+            crossfade: LoopCrossfade::default(),
+            zero_crossing_snap: false,
+            region_edge: RegionEdge::Wrap,
+            scan_mode: ScanMode::Forward,
+            scan_velocity: 1.0,
+            scan_target: 0.0,
+            jump_countdown: 0.0,
         }
     }
 
-    pub fn load_samples(&mut self, samples: Vec<f32>) {
-        self.samples = samples;
+    // Mono or stereo (see SampleData::into_stereo for more channels).
+    // Returns the replaced samples: the caller chooses the thread that frees them.
+    pub fn load_samples(&mut self, samples: SampleData) -> SampleData {
+        let previous = std::mem::replace(&mut self.samples, samples);
         self.index = 0.0;
         self.start = 0;
         self.end = self.samples.len();
+        previous
     }
 
     pub fn set_level(&mut self, level: f32) {
@@ -80,10 +139,11 @@ impl Granulator {
     }
 
     pub fn set_start(&mut self, start: usize) {
-        self.start = start;
+        self.start = self.snap(start);
     }
 
     pub fn set_end(&mut self, end: usize) {
+        let end = self.snap(end);
         self.end = end;
         if self.index >= end as f32 {
             self.index = self.start as f32
@@ -104,10 +164,60 @@ impl Granulator {
         self.grains.set_grain_density(self.sample_rate / grains_per_sec);
     }
 
+    pub fn set_grain_scheduling(&mut self, scheduling: GrainScheduling) {
+        self.grains.set_scheduling(scheduling);
+    }
+
+    // Random deviation of the intervals between grains, 0..1
+    pub fn set_grain_jitter(&mut self, jitter: f32) {
+        self.grains.set_jitter(jitter);
+    }
+
     pub fn set_scan_spread(&mut self,scan_spread: f32){
         self.scan_spread = scan_spread;
     }
 
+    // Probability for a grain to read the sample channels swapped, 0..1
+    pub fn set_channel_swap(&mut self, probability: f32) {
+        self.grains.set_channel_swap(probability);
+    }
+
+    // Random balance of each grain, 0..1
+    pub fn set_balance_spread(&mut self, spread: f32) {
+        self.grains.set_balance_spread(spread);
+    }
+
+    // Maximum number of grains playing at once, 1..MAX_GRAINS
+    pub fn set_grain_pool_size(&mut self, pool_size: usize) {
+        self.grains.set_pool_size(pool_size);
+    }
+
+    // What happens to a new grain when the pool is full.
+    pub fn set_stealing_policy(&mut self, policy: StealingPolicy) {
+        self.grains.set_stealing_policy(policy);
+    }
+
+    pub fn active_grain_count(&self) -> usize {
+        self.grains.active_count()
+    }
+
+    // Per grain random variations, see Grains.
+    pub fn set_grain_pitch_spread(&mut self, semi_tones: f32, quantization: PitchQuantization) {
+        self.grains.set_pitch_spread(semi_tones, quantization);
+    }
+
+    pub fn set_grain_gain_spread(&mut self, spread: f32) {
+        self.grains.set_gain_spread(spread);
+    }
+
+    pub fn set_grain_duration_spread(&mut self, spread: f32) {
+        self.grains.set_duration_spread(spread);
+    }
+
+    pub fn set_grain_reverse_probability(&mut self, probability: f32) {
+        self.grains.set_reverse_probability(probability);
+    }
+
     pub fn set_grain_attack_slope(&mut self,v: f32){
         self.grain_attack_slope = v;
     }
@@ -118,102 +228,180 @@ impl Granulator {
         self.grain_release_slope = v;
     }
 
-    fn next_index(&self) -> f32 {
-        let new_index = self.index + self.step;
+    // Shape of the grain envelope, the table is computed here (not in the audio loop).
+    pub fn set_grain_window(&mut self, window: GrainWindow) {
+        self.grain_window = GrainWindowTable::new(window);
+    }
+
+    // Crossfade of the scan head at the loop point, length in samples (0: none).
+    // Close to the end, grains are spawned after the start with an increasing probability.
+    pub fn set_loop_crossfade(&mut self, length: usize, curve: CrossfadeCurve) {
+        self.crossfade = LoopCrossfade::new(length, curve);
+    }
+
+    pub fn set_scan_mode(&mut self, scan_mode: ScanMode) {
+        self.scan_mode = scan_mode;
+        self.jump_countdown = 0.0;
+        if scan_mode == ScanMode::Manual {
+            // Stay in place until a position is set.
+            self.scan_target = self.index;
+        }
+    }
+
+    // Target of the Manual mode, 0..1 of the region.
+    pub fn set_scan_position(&mut self, position: f32) {
+        let last = (self.end as f32 - 1.0).max(self.start as f32);
+        self.scan_target = self.start as f32 + position.clamp(0.0, 1.0) * (last - self.start as f32);
+    }
+
+    // What the grains do at the start and end points.
+    pub fn set_region_edge(&mut self, edge: RegionEdge) {
+        self.region_edge = edge;
+    }
+
+    // Move the start and end points to the closest zero crossing when they are set.
+    pub fn set_zero_crossing_snap(&mut self, zero_crossing_snap: bool) {
+        self.zero_crossing_snap = zero_crossing_snap;
+    }
+
+    fn snap(&self, index: usize) -> usize {
+        if self.zero_crossing_snap {
+            find_zero_crossing(self.samples.channel(0), index, MAX_ZERO_CROSSING_DISTANCE)
+        } else {
+            index
+        }
+    }
+
+    fn crossfade_length(&self) -> f32 {
         // -1 because of the interpolation.
+        let loop_length = self.end as f32 - 1.0 - self.start as f32;
+        self.crossfade.effective_length(loop_length)
+    }
+
+    fn next_index(&mut self) -> f32 {
+        let glide = 1.0 - (-1.0 / (SCAN_GLIDE_TIME * self.sample_rate)).exp();
+        let target_velocity = match self.scan_mode {
+            ScanMode::Forward => self.step,
+            ScanMode::Reverse => -self.step,
+            _ => 0.0,
+        };
+        self.scan_velocity += glide * (target_velocity - self.scan_velocity);
+
+        let mut new_index = self.index + self.scan_velocity;
+        match self.scan_mode {
+            ScanMode::RandomWalk(size) => {
+                new_index += size * (2.0 * fastrand::f32() - 1.0);
+            }
+            ScanMode::Jump(interval) => {
+                self.jump_countdown -= 1.0;
+                if self.jump_countdown <= 0.0 {
+                    self.jump_countdown = (interval * self.sample_rate).max(1.0);
+                    self.set_scan_position(fastrand::f32());
+                }
+                new_index += glide * (self.scan_target - new_index);
+            }
+            ScanMode::Manual => {
+                new_index += glide * (self.scan_target - new_index);
+            }
+            _ => {}
+        }
+
+        // -1 because of the interpolation.
+        let fade_start = self.end as f32 - 1.0 - self.crossfade_length();
         if new_index >= self.end as f32 - 1.0 {
-            self.start as f32
+            // The faded in part was already played during the crossfade.
+            self.start as f32 + self.crossfade_length()
+        } else if new_index < self.start as f32 {
+            // Backward, the start matches the beginning of the crossfade.
+            (new_index - self.start as f32 + fade_start).max(self.start as f32)
         } else {
             new_index
         }
     }
 
-    pub fn current_index(&self) -> f32 {
-        self.index
+    // Location of the next grain: the scan head, or its position after the loop point
+    // while crossfading (with the power share of the fade in).
+    fn scan_location(&self) -> f32 {
+        let fade = self.crossfade_length();
+        let fade_start = self.end as f32 - 1.0 - fade;
+        if fade < 1.0 || self.index < fade_start {
+            return self.index;
+        }
+        let (_, fade_in) = self.crossfade.gains((self.index - fade_start) / fade);
+        let probability = match self.crossfade.curve() {
+            CrossfadeCurve::Linear => fade_in,
+            CrossfadeCurve::EqualPower => fade_in * fade_in,
+        };
+        if fastrand::f32() < probability {
+            self.start as f32 + (self.index - fade_start)
+        } else {
+            self.index
+        }
     }
 
-    #[inline] 
-    fn get_value_at(&self,position: usize) -> f32 {
-        self.samples[position]
+    pub fn current_index(&self) -> f32 {
+        self.index
     }
 
     #[inline] 
-    fn get_interpolated_value_at(&self,position: f32) -> f32 {
-        let idx = position as usize;
-        let w = position.fract();
-        let v0 = self.samples[idx];
-        let v1 = self.samples[idx + 1]; 
-        (1.0 - w) * v0 + w * v1
+    fn get_value_at(&self,position: usize) -> (f32, f32) {
+        self.samples.frame(position)
     }
 }
 
 impl Module for Granulator {
     fn process(&mut self) {
-        let mut wrapped_buf_left = self.output_left.try_borrow_mut().unwrap();
-        let mut wrapped_buf_right = self.output_right.try_borrow_mut().unwrap();
+        let output_left = self.output_left.clone();
+        let output_right = self.output_right.clone();
+        let mut wrapped_buf_left = output_left.try_borrow_mut().unwrap();
+        let mut wrapped_buf_right = output_right.try_borrow_mut().unwrap();
 
         let buf_left = wrapped_buf_left.get_mut();
         let buf_right = wrapped_buf_right.get_mut();
         let grain_step = self.grain_step;
-        let start = 0.0;
-        let end = self.samples.len() as f32 - 1.0;
+        // -1 because of the interpolation.
+        let region = GrainRegion {
+            start: self.start as f32,
+            end: (self.end as f32 - 1.0).max(self.start as f32),
+            edge: self.region_edge,
+        };
         let attack_slope = self.grain_attack_slope;
         let sustain_duration = self.grain_sustain_duration;
         let release_slope = self.grain_release_slope;
 
-        for (b_l,b_r) in buf_left.iter_mut().zip(buf_right) {
-            self.grains.grain_scheduler(grain_step,
-                self.index, 
-                self.pan_spread, 
-                self.scan_spread * (self.samples.len() as f32/2.0), 
-                attack_slope, 
-                sustain_duration,
-                release_slope);
-
-            let mut l_value = 0.0;
-            let mut r_value = 0.0;
-            let mut left = 0.0;
-            let mut right = 0.0;            
-            {
-                let result = &mut self.vec_result;
-                result.clear();
-                for g in self.grains.grains.iter_mut() {
-                    let optional_result = g.next(grain_step,start,end);
-                    if let Some(res) = optional_result {
-                        result.push(res);
-                    }
-                }
-
-                // self.grains.grains
-                //     .iter_mut()
-                //     .map(|g| g.next(grain_step,start,end))
-                //     .filter_map(|g| g)
-                //     .for_each(|src|{
-                //         result.push(src);
-                //     });
-            }
-            for r in self.vec_result.iter() {
-                l_value += r.l_value;
-                r_value += r.r_value;
-                let sample_value = self.get_interpolated_value_at(r.position);
-                left += r.l_value * sample_value;
-                right += r.r_value * sample_value;
+        // The scan head and the scheduler run sample by sample, the grains are rendered
+        // over the parts of the chunk between the starts of new grains.
+        // Grains waiting for a full pool start on the frame after the end of a grain.
+        self.mix.clear();
+        let mut segment_start = 0;
+        let mut free_frame = self.grains.free_frame(0..N_SAMPLES_PER_CHUNK, &self.grain_window);
+        for i in 0..N_SAMPLES_PER_CHUNK {
+            if self.grains.tick(grain_step) || free_frame.is_some_and(|f| f <= i) {
+                self.grains.render(segment_start..i, grain_step, &region, &self.grain_window, &self.samples, &mut self.mix);
+                segment_start = i;
+                let scan_location = self.scan_location();
+                self.grains.start_pending(scan_location,
+                    self.pan_spread, 
+                    self.scan_spread * (region.length() / 2.0), 
+                    attack_slope, 
+                    sustain_duration,
+                    release_slope);
+                free_frame = self.grains.free_frame(i..N_SAMPLES_PER_CHUNK, &self.grain_window);
             }
+            self.index = self.next_index();
+        }
+        self.grains.render(segment_start..N_SAMPLES_PER_CHUNK, grain_step, &region, &self.grain_window, &self.samples, &mut self.mix);
 
-            if l_value > 1.0 {
-                left = left/l_value;
-            } 
-            if r_value > 1.0 {
-                right = right/r_value;
-            } 
+        for (i, (b_l,b_r)) in buf_left.iter_mut().zip(buf_right).enumerate() {
+            let (left, right) = self.mix.frame(i);
             *b_l  = self.level * left;
             *b_r = self.level * right;
-            self.index = self.next_index();
         }
     }
 
     fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        self.grains.set_sample_rate(sample_rate);
     }
 }
 
@@ -2,7 +2,11 @@ mod basic;
 mod granular;
 mod wav;
 mod grains;
+mod phase_vocoder;
+mod spectral;
 pub use grains::*;
+pub use phase_vocoder::*;
+pub use spectral::*;
 
 pub use basic::*;
 pub use granular::*;
@@ -0,0 +1,458 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    f32::consts::PI,
+    rc::Rc,
+    sync::Arc,
+};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::core::{
+    window, Buffer, Module, MonoGenerator, SampleData, SharedBuffer, StereoGenerator, WindowType,
+};
+
+pub(crate) const FFT_SIZE: usize = 2048;
+pub(crate) const HOP_SIZE: usize = FFT_SIZE / 4;
+pub(crate) const BINS: usize = FFT_SIZE / 2 + 1;
+// Sum of the squared hann window over the overlapping frames (analysis * synthesis windows).
+const OVERLAP_GAIN: f32 = 1.5;
+
+pub(crate) fn princarg(phase: f32) -> f32 {
+    phase - 2.0 * PI * ((phase + PI) / (2.0 * PI)).floor()
+}
+
+// Windowed frame of the [start, end) region centered on `center`, wrapped around the region.
+pub(crate) fn read_windowed_frame(
+    samples: &[f32],
+    start: usize,
+    end: usize,
+    center: isize,
+    window: &[f32],
+    frame: &mut [Complex<f32>],
+) {
+    let length = end as isize - start as isize;
+    let size = frame.len() as isize;
+    for (i, (c, w)) in frame.iter_mut().zip(window).enumerate() {
+        let sample = if length > 0 {
+            let index = (center - size / 2 + i as isize - start as isize).rem_euclid(length)
+                + start as isize;
+            samples[index as usize]
+        } else {
+            0.0
+        };
+        *c = Complex::new(sample * w, 0.0);
+    }
+}
+
+// FFT plans, window and scratch buffer shared by the channels of the spectral engines.
+pub(crate) struct Stft {
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    scratch: Vec<Complex<f32>>,
+    window: Vec<f32>,
+    // Expected phase advance of each bin over a hop.
+    pub(crate) bin_advance: Vec<f32>,
+}
+
+impl Stft {
+    pub(crate) fn new() -> Self {
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(FFT_SIZE);
+        let ifft = planner.plan_fft_inverse(FFT_SIZE);
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+        let bin_advance = (0..BINS)
+            .map(|k| 2.0 * PI * (k * HOP_SIZE) as f32 / FFT_SIZE as f32)
+            .collect();
+        Stft {
+            fft,
+            ifft,
+            scratch: vec![Complex::default(); scratch_len],
+            window: window(WindowType::Hann, FFT_SIZE),
+            bin_advance,
+        }
+    }
+
+    // Spectrum of the [start, end) region around center.
+    pub(crate) fn analyze(
+        &mut self,
+        samples: &[f32],
+        start: usize,
+        end: usize,
+        center: isize,
+        frame: &mut [Complex<f32>],
+    ) {
+        read_windowed_frame(samples, start, end, center, &self.window, frame);
+        self.fft.process_with_scratch(frame, &mut self.scratch);
+    }
+
+    // Resynthesises the first BINS bins of frame and adds them to overlap_add,
+    // then moves the first hop of overlap_add to hop.
+    pub(crate) fn synthesize(
+        &mut self,
+        frame: &mut [Complex<f32>],
+        overlap_add: &mut [f32],
+        hop: &mut [f32],
+    ) {
+        for k in BINS..FFT_SIZE {
+            frame[k] = frame[FFT_SIZE - k].conj();
+        }
+        self.ifft.process_with_scratch(frame, &mut self.scratch);
+
+        let gain = 1.0 / (OVERLAP_GAIN * FFT_SIZE as f32);
+        for ((o, c), w) in overlap_add.iter_mut().zip(frame.iter()).zip(&self.window) {
+            *o += c.re * w * gain;
+        }
+        hop.copy_from_slice(&overlap_add[..HOP_SIZE]);
+        overlap_add.copy_within(HOP_SIZE.., 0);
+        overlap_add[FFT_SIZE - HOP_SIZE..]
+            .iter_mut()
+            .for_each(|v| *v = 0.0);
+    }
+}
+
+// Analysis and synthesis state of one side.
+struct VocoderChannel {
+    frame: Vec<Complex<f32>>,
+    previous_frame: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    previous_magnitudes: Vec<f32>,
+    phases: Vec<f32>,
+    synthesis_phases: Vec<f32>,
+    peaks: Vec<usize>,
+    overlap_add: Vec<f32>,
+    hop: Vec<f32>,
+    stream: VecDeque<f32>,
+}
+
+impl VocoderChannel {
+    fn new() -> Self {
+        VocoderChannel {
+            frame: vec![Complex::default(); FFT_SIZE],
+            previous_frame: vec![Complex::default(); FFT_SIZE],
+            magnitudes: vec![0.0; BINS],
+            previous_magnitudes: vec![0.0; BINS],
+            phases: vec![0.0; BINS],
+            synthesis_phases: vec![0.0; BINS],
+            peaks: Vec::with_capacity(BINS),
+            overlap_add: vec![0.0; FFT_SIZE],
+            hop: vec![0.0; HOP_SIZE],
+            stream: VecDeque::with_capacity(2 * FFT_SIZE),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.overlap_add.iter_mut().for_each(|v| *v = 0.0);
+        self.previous_magnitudes.iter_mut().for_each(|v| *v = 0.0);
+        self.stream.clear();
+    }
+
+    fn analyze(&mut self, stft: &mut Stft, samples: &[f32], start: usize, end: usize, center: isize) {
+        stft.analyze(samples, start, end, center, &mut self.frame);
+        stft.analyze(samples, start, end, center - HOP_SIZE as isize, &mut self.previous_frame);
+        for k in 0..BINS {
+            self.magnitudes[k] = self.frame[k].norm();
+            self.phases[k] = self.frame[k].arg();
+        }
+    }
+
+    // (positive flux, total magnitude) since the previous frame.
+    fn flux(&self) -> (f32, f32) {
+        let mut flux = 0.0;
+        let mut total = 0.0;
+        for (m, p) in self.magnitudes.iter().zip(&self.previous_magnitudes) {
+            flux += (m - p).max(0.0);
+            total += m;
+        }
+        (flux, total)
+    }
+
+    fn find_peaks(&mut self) {
+        self.peaks.clear();
+        let m = &self.magnitudes;
+        for k in 2..BINS - 2 {
+            if m[k] > m[k - 1] && m[k] >= m[k + 1] && m[k] > m[k - 2] && m[k] >= m[k + 2] {
+                self.peaks.push(k);
+            }
+        }
+    }
+
+    fn propagate_phase(&mut self, k: usize, bin_advance: &[f32]) {
+        let previous_phase = self.previous_frame[k].arg();
+        let deviation = princarg(self.phases[k] - previous_phase - bin_advance[k]);
+        self.synthesis_phases[k] = princarg(self.synthesis_phases[k] + bin_advance[k] + deviation);
+    }
+
+    // reset: start again from the analysed phases (first frame or transient).
+    fn update_phases(&mut self, reset: bool, phase_locking: bool, bin_advance: &[f32]) {
+        if reset {
+            self.synthesis_phases.copy_from_slice(&self.phases);
+            return;
+        }
+
+        if phase_locking {
+            self.find_peaks();
+        }
+        if !phase_locking || self.peaks.is_empty() {
+            for k in 0..BINS {
+                self.propagate_phase(k, bin_advance);
+            }
+            return;
+        }
+        for i in 0..self.peaks.len() {
+            let peak = self.peaks[i];
+            self.propagate_phase(peak, bin_advance);
+        }
+        // Identity phase locking: every bin keeps its phase offset to the peak of its region.
+        let mut region = 0;
+        for k in 0..BINS {
+            while region + 1 < self.peaks.len()
+                && k > (self.peaks[region] + self.peaks[region + 1]) / 2
+            {
+                region += 1;
+            }
+            let peak = self.peaks[region];
+            if k != peak {
+                self.synthesis_phases[k] =
+                    self.synthesis_phases[peak] + self.phases[k] - self.phases[peak];
+            }
+        }
+    }
+
+    fn synthesize(&mut self, stft: &mut Stft) {
+        self.previous_magnitudes.copy_from_slice(&self.magnitudes);
+        for k in 0..BINS {
+            self.frame[k] = Complex::from_polar(self.magnitudes[k], self.synthesis_phases[k]);
+        }
+        stft.synthesize(&mut self.frame, &mut self.overlap_add, &mut self.hop);
+        self.stream.extend(&self.hop);
+    }
+
+    // Linear interpolation of the stream.
+    #[inline]
+    fn read(&self, i: usize, w: f32) -> f32 {
+        (1.0 - w) * self.stream[i] + w * self.stream[i + 1]
+    }
+}
+
+// Plays a loaded sample with independent time and pitch ratios.
+// Each frame is analysed twice (at the read position and one hop before) so the instantaneous
+// frequencies do not depend on the time ratio, then resynthesised at a fixed hop.
+// Pitch is shifted by stretching the vocoder output and resampling it.
+// Stereo samples are processed side by side with the same transients.
+pub struct PhaseVocoder {
+    samples: SampleData,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    // Average of the two sides.
+    output: SharedBuffer,
+    sample_rate: f32,
+    level: f32,
+    start: usize, // Included
+    end: usize,   // Excluded
+    position: f32,
+    time_ratio: f32,
+    pitch_ratio: f32,
+    phase_locking: bool,
+    transient_preservation: bool,
+    transient_threshold: f32,
+    stft: Stft,
+    // Left and right, only the first one is used for mono samples.
+    channels: [VocoderChannel; 2],
+    stream_position: f32,
+    reset_phases: bool,
+}
+
+impl PhaseVocoder {
+    pub fn new() -> Self {
+        let samples = SampleData::from_mono(vec![0.0, 0.0]);
+        let end = samples.len();
+
+        Self {
+            samples,
+            output_left: Rc::new(RefCell::new(Buffer::new())),
+            output_right: Rc::new(RefCell::new(Buffer::new())),
+            output: Rc::new(RefCell::new(Buffer::new())),
+            sample_rate: 44_100.0,
+            level: 1.0,
+            start: 0,
+            end,
+            position: 0.0,
+            time_ratio: 1.0,
+            pitch_ratio: 1.0,
+            phase_locking: true,
+            transient_preservation: true,
+            transient_threshold: 0.3,
+            stft: Stft::new(),
+            channels: [VocoderChannel::new(), VocoderChannel::new()],
+            stream_position: 0.0,
+            reset_phases: true,
+        }
+    }
+
+    // Mono or stereo (see SampleData::into_stereo for more channels).
+    // Returns the replaced samples: the caller chooses the thread that frees them.
+    pub fn load_samples(&mut self, samples: SampleData) -> SampleData {
+        let previous = std::mem::replace(&mut self.samples, samples);
+        self.start = 0;
+        self.end = self.samples.len();
+        self.position = 0.0;
+        self.reset();
+        previous
+    }
+
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    pub fn set_start(&mut self, start: usize) {
+        self.start = start.min(self.samples.len());
+    }
+
+    pub fn set_end(&mut self, end: usize) {
+        self.end = end.min(self.samples.len());
+        if self.position >= self.end as f32 {
+            self.position = self.start as f32;
+        }
+    }
+
+    // Duration ratio, 2.0 plays twice as slow (0.1..10)
+    pub fn set_time_ratio(&mut self, ratio: f32) {
+        self.time_ratio = ratio.clamp(0.1, 10.0);
+    }
+
+    // Frequency ratio, 2.0 is an octave up (0.25..4)
+    pub fn set_pitch_ratio(&mut self, ratio: f32) {
+        self.pitch_ratio = ratio.clamp(0.25, 4.0);
+    }
+
+    // Keep the phases of the bins around each spectral peak coherent (less phasiness).
+    pub fn set_phase_locking(&mut self, phase_locking: bool) {
+        self.phase_locking = phase_locking;
+    }
+
+    // Reset the synthesis phases on onsets so attacks are not smeared.
+    pub fn set_transient_preservation(&mut self, transient_preservation: bool) {
+        self.transient_preservation = transient_preservation;
+    }
+
+    // Normalised spectral flux above which a frame is an onset, 0..1
+    pub fn set_transient_threshold(&mut self, threshold: f32) {
+        self.transient_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    pub fn current_index(&self) -> f32 {
+        self.position
+    }
+
+    fn channel_count(&self) -> usize {
+        self.samples.channel_count().min(2)
+    }
+
+    fn reset(&mut self) {
+        self.channels.iter_mut().for_each(|c| c.reset());
+        self.stream_position = 0.0;
+        self.reset_phases = true;
+    }
+
+    // The flux of all the sides, so they reset their phases together.
+    fn is_transient(&self) -> bool {
+        let (flux, total) = self.channels[..self.channel_count()]
+            .iter()
+            .map(|c| c.flux())
+            .fold((0.0, 0.0), |(f, t), (cf, ct)| (f + cf, t + ct));
+        total > 1e-6 && flux / total > self.transient_threshold
+    }
+
+    fn advance(&mut self) {
+        // Stretched by time * pitch, then resampled by pitch.
+        let analysis_hop = HOP_SIZE as f32 / (self.time_ratio * self.pitch_ratio);
+        let length = self.end.saturating_sub(self.start) as f32;
+        self.position += analysis_hop;
+        if length > 0.0 && self.position >= self.end as f32 {
+            self.position = self.start as f32 + (self.position - self.end as f32) % length;
+        } else if self.position < self.start as f32 {
+            self.position = self.start as f32;
+        }
+    }
+
+    fn next_frame(&mut self) {
+        let count = self.channel_count();
+        let center = self.position.round() as isize;
+        let (left, right) = self.samples.stereo_channels();
+        for (channel, samples) in self.channels[..count].iter_mut().zip([left, right]) {
+            channel.analyze(&mut self.stft, samples, self.start, self.end, center);
+        }
+
+        let reset = self.reset_phases || (self.transient_preservation && self.is_transient());
+        self.reset_phases = false;
+        for channel in self.channels[..count].iter_mut() {
+            channel.update_phases(reset, self.phase_locking, &self.stft.bin_advance);
+            channel.synthesize(&mut self.stft);
+        }
+        self.advance();
+    }
+}
+
+impl Default for PhaseVocoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for PhaseVocoder {
+    fn process(&mut self) {
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let output = self.output.clone();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+        let mut output = output.try_borrow_mut().unwrap();
+        let outputs = output_left
+            .get_mut()
+            .iter_mut()
+            .zip(output_right.get_mut())
+            .zip(output.get_mut());
+        let right_channel = self.channel_count() - 1;
+
+        for ((out_l, out_r), out) in outputs {
+            // -2 because of the interpolation.
+            while self.channels[0].stream.len() < self.stream_position as usize + 2 {
+                self.next_frame();
+            }
+            let i = self.stream_position as usize;
+            let w = self.stream_position.fract();
+            *out_l = self.level * self.channels[0].read(i, w);
+            *out_r = self.level * self.channels[right_channel].read(i, w);
+            *out = 0.5 * (*out_l + *out_r);
+
+            self.stream_position += self.pitch_ratio;
+            let consumed = self.stream_position as usize;
+            for channel in self.channels[..=right_channel].iter_mut() {
+                channel.stream.drain(..consumed);
+            }
+            self.stream_position -= consumed as f32;
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl MonoGenerator for PhaseVocoder {
+    fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+}
+
+impl StereoGenerator for PhaseVocoder {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
@@ -0,0 +1,328 @@
+use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+
+use rustfft::num_complex::Complex;
+
+use super::{princarg, Stft, BINS, FFT_SIZE, HOP_SIZE};
+use crate::core::{Buffer, Module, MonoGenerator, SampleData, SharedBuffer, StereoGenerator};
+
+const MAX_SMEAR_BINS: usize = 32;
+
+// Analysis and synthesis state of one side.
+struct FreezeChannel {
+    frame: Vec<Complex<f32>>,
+    previous_frame: Vec<Complex<f32>>,
+    target_magnitudes: Vec<f32>,
+    phase_advances: Vec<f32>,
+    magnitudes: Vec<f32>,
+    smeared: Vec<f32>,
+    synthesis_phases: Vec<f32>,
+    overlap_add: Vec<f32>,
+    hop_output: Vec<f32>,
+}
+
+impl FreezeChannel {
+    fn new() -> Self {
+        FreezeChannel {
+            frame: vec![Complex::default(); FFT_SIZE],
+            previous_frame: vec![Complex::default(); FFT_SIZE],
+            target_magnitudes: vec![0.0; BINS],
+            phase_advances: vec![0.0; BINS],
+            magnitudes: vec![0.0; BINS],
+            smeared: vec![0.0; BINS],
+            synthesis_phases: vec![0.0; BINS],
+            overlap_add: vec![0.0; FFT_SIZE],
+            hop_output: vec![0.0; HOP_SIZE],
+        }
+    }
+
+    fn analyze(
+        &mut self,
+        stft: &mut Stft,
+        samples: &[f32],
+        (start, end): (usize, usize),
+        center: isize,
+        reset_phases: bool,
+    ) {
+        stft.analyze(samples, start, end, center, &mut self.frame);
+        stft.analyze(samples, start, end, center - HOP_SIZE as isize, &mut self.previous_frame);
+
+        for k in 0..BINS {
+            self.target_magnitudes[k] = self.frame[k].norm();
+            let deviation = princarg(
+                self.frame[k].arg() - self.previous_frame[k].arg() - stft.bin_advance[k],
+            );
+            self.phase_advances[k] = stft.bin_advance[k] + deviation;
+        }
+        // Start from the analysed phases so the bins of each partial stay coherent.
+        if reset_phases {
+            for k in 0..BINS {
+                self.synthesis_phases[k] = self.frame[k].arg() - self.phase_advances[k];
+            }
+        }
+    }
+
+    fn smear_magnitudes(&mut self, radius: usize) {
+        if radius == 0 {
+            self.smeared.copy_from_slice(&self.magnitudes);
+            return;
+        }
+        // Running box filter over the bins
+        let mut sum: f32 = self.magnitudes[..radius.min(BINS)].iter().sum();
+        for k in 0..BINS {
+            if k + radius < BINS {
+                sum += self.magnitudes[k + radius];
+            }
+            if k > radius {
+                sum -= self.magnitudes[k - radius - 1];
+            }
+            let count = (k + radius).min(BINS - 1) + 1 - k.saturating_sub(radius);
+            self.smeared[k] = sum / count as f32;
+        }
+    }
+
+    // random_phases and bin_gains are drawn once per hop for all the sides.
+    fn next_hop(
+        &mut self,
+        stft: &mut Stft,
+        blur: f32,
+        smear: usize,
+        random_phases: &[f32],
+        bin_gains: &[f32],
+    ) {
+        for (m, t) in self.magnitudes.iter_mut().zip(&self.target_magnitudes) {
+            *m = blur * *m + (1.0 - blur) * t;
+        }
+        self.smear_magnitudes(smear);
+
+        for k in 0..BINS {
+            self.synthesis_phases[k] =
+                princarg(self.synthesis_phases[k] + self.phase_advances[k] + random_phases[k]);
+            let magnitude = self.smeared[k] * bin_gains[k];
+            self.frame[k] = Complex::from_polar(magnitude, self.synthesis_phases[k]);
+        }
+        stft.synthesize(&mut self.frame, &mut self.overlap_add, &mut self.hop_output);
+    }
+}
+
+// Resynthesises the spectrum of the loaded sample at the scan position.
+// Frozen, the last captured frame is held indefinitely, its phases optionally randomized.
+// Spectral granulation keeps a random subset of the bins on each frame, blur smooths the
+// magnitudes over time and smear spreads them across neighbouring bins.
+// Stereo samples are processed side by side with the same random bins and phases.
+pub struct SpectralFreeze {
+    samples: SampleData,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    // Average of the two sides.
+    output: SharedBuffer,
+    sample_rate: f32,
+    level: f32,
+    start: usize, // Included
+    end: usize,   // Excluded
+    position: f32,
+    frozen: bool,
+    capture_pending: bool,
+    reset_phases: bool,
+    phase_randomization: f32,
+    bin_density: f32,
+    blur: f32,
+    smear: usize,
+    stft: Stft,
+    // Left and right, only the first one is used for mono samples.
+    channels: [FreezeChannel; 2],
+    random_phases: Vec<f32>,
+    bin_gains: Vec<f32>,
+    hop_index: usize,
+}
+
+impl SpectralFreeze {
+    pub fn new() -> Self {
+        let samples = SampleData::from_mono(vec![0.0, 0.0]);
+        let end = samples.len();
+
+        Self {
+            samples,
+            output_left: Rc::new(RefCell::new(Buffer::new())),
+            output_right: Rc::new(RefCell::new(Buffer::new())),
+            output: Rc::new(RefCell::new(Buffer::new())),
+            sample_rate: 44_100.0,
+            level: 1.0,
+            start: 0,
+            end,
+            position: 0.0,
+            frozen: false,
+            capture_pending: false,
+            reset_phases: true,
+            phase_randomization: 1.0,
+            bin_density: 1.0,
+            blur: 0.0,
+            smear: 0,
+            stft: Stft::new(),
+            channels: [FreezeChannel::new(), FreezeChannel::new()],
+            random_phases: vec![0.0; BINS],
+            bin_gains: vec![0.0; BINS],
+            hop_index: HOP_SIZE,
+        }
+    }
+
+    // Mono or stereo (see SampleData::into_stereo for more channels).
+    // Returns the replaced samples: the caller chooses the thread that frees them.
+    pub fn load_samples(&mut self, samples: SampleData) -> SampleData {
+        let previous = std::mem::replace(&mut self.samples, samples);
+        self.start = 0;
+        self.end = self.samples.len();
+        self.position = 0.0;
+        self.capture_pending = self.frozen;
+        self.reset_phases = true;
+        previous
+    }
+
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    pub fn set_start(&mut self, start: usize) {
+        self.start = start.min(self.samples.len());
+    }
+
+    pub fn set_end(&mut self, end: usize) {
+        self.end = end.min(self.samples.len());
+    }
+
+    // Scan position (sample index) where the frames are captured.
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position;
+    }
+
+    // Freezing captures the frame at the current scan position and holds it.
+    pub fn set_freeze(&mut self, frozen: bool) {
+        if frozen && !self.frozen {
+            self.capture_pending = true;
+            self.reset_phases = true;
+        }
+        self.frozen = frozen;
+    }
+
+    // Capture a new frame at the current scan position, keeping the freeze.
+    pub fn capture(&mut self) {
+        self.capture_pending = true;
+        self.reset_phases = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    // Random phase offset added on each frame, 0 (coherent)..1 (fully random)
+    pub fn set_phase_randomization(&mut self, amount: f32) {
+        self.phase_randomization = amount.clamp(0.0, 1.0);
+    }
+
+    // Probability for a bin to be kept on each frame, 0..1 (1: all bins)
+    pub fn set_bin_density(&mut self, density: f32) {
+        self.bin_density = density.clamp(0.0, 1.0);
+    }
+
+    // Smoothing of the magnitudes over time, 0..0.99
+    pub fn set_blur(&mut self, blur: f32) {
+        self.blur = blur.clamp(0.0, 0.99);
+    }
+
+    // Spread of the magnitudes across neighbouring bins, 0..1 (up to 32 bins on each side)
+    pub fn set_smear(&mut self, smear: f32) {
+        self.smear = (smear.clamp(0.0, 1.0) * MAX_SMEAR_BINS as f32).round() as usize;
+    }
+
+    fn channel_count(&self) -> usize {
+        self.samples.channel_count().min(2)
+    }
+
+    fn next_hop(&mut self) {
+        let count = self.channel_count();
+        if !self.frozen || self.capture_pending {
+            let center = self.position.round() as isize;
+            let (left, right) = self.samples.stereo_channels();
+            for (channel, samples) in self.channels[..count].iter_mut().zip([left, right]) {
+                let region = (self.start, self.end);
+                channel.analyze(&mut self.stft, samples, region, center, self.reset_phases);
+            }
+            self.capture_pending = false;
+            self.reset_phases = false;
+        }
+
+        // Keep the energy constant whatever the bin density.
+        let density = self.bin_density;
+        let density_gain = if density > 0.0 { 1.0 / density.sqrt() } else { 0.0 };
+        let randomization = self.phase_randomization * PI;
+        for (phase, gain) in self.random_phases.iter_mut().zip(self.bin_gains.iter_mut()) {
+            *phase = randomization * (2.0 * fastrand::f32() - 1.0);
+            *gain = if density >= 1.0 || fastrand::f32() < density {
+                density_gain
+            } else {
+                0.0
+            };
+        }
+        for channel in self.channels[..count].iter_mut() {
+            channel.next_hop(
+                &mut self.stft,
+                self.blur,
+                self.smear,
+                &self.random_phases,
+                &self.bin_gains,
+            );
+        }
+        self.hop_index = 0;
+    }
+}
+
+impl Default for SpectralFreeze {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SpectralFreeze {
+    fn process(&mut self) {
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let output = self.output.clone();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+        let mut output = output.try_borrow_mut().unwrap();
+        let outputs = output_left
+            .get_mut()
+            .iter_mut()
+            .zip(output_right.get_mut())
+            .zip(output.get_mut());
+
+        for ((out_l, out_r), out) in outputs {
+            if self.hop_index == HOP_SIZE {
+                self.next_hop();
+            }
+            let right_channel = self.channel_count() - 1;
+            *out_l = self.level * self.channels[0].hop_output[self.hop_index];
+            *out_r = self.level * self.channels[right_channel].hop_output[self.hop_index];
+            *out = 0.5 * (*out_l + *out_r);
+            self.hop_index += 1;
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl MonoGenerator for SpectralFreeze {
+    fn get_output(&self) -> SharedBuffer {
+        self.output.clone()
+    }
+}
+
+impl StereoGenerator for SpectralFreeze {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
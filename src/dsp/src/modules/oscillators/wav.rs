@@ -1,9 +1,40 @@
 use std::{cell::RefCell, f32, rc::Rc};
 
-use crate::core::{Buffer, Module, MonoGenerator, SharedBuffer, StereoGenerator};
+use crate::core::{
+    find_zero_crossing, Buffer, CrossfadeCurve, LoopCrossfade, Module, MonoGenerator, SampleData,
+    SharedBuffer, StereoGenerator, MAX_ZERO_CROSSING_DISTANCE,
+};
+
+// Fade out applied when the gate is released, avoids clicks.
+const RELEASE_MS: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopMode {
+    // Plays once from start to end, then stops.
+    OneShot,
+    Forward,
+    // Back and forth between start and end.
+    PingPong,
+    // Plays once from end to start, then stops.
+    Reverse,
+    ReverseLoop,
+}
+
+impl LoopMode {
+    fn is_reversed(&self) -> bool {
+        matches!(self, LoopMode::Reverse | LoopMode::ReverseLoop)
+    }
+
+    fn is_one_shot(&self) -> bool {
+        matches!(self, LoopMode::OneShot | LoopMode::Reverse)
+    }
+}
 
 pub struct Samples {
-    samples: Vec<f32>,
+    samples: SampleData,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    // Average of the two sides.
     output: SharedBuffer,
     index: f32,
     step: f32,
@@ -11,6 +42,16 @@ pub struct Samples {
     level: f32,
     start: usize, // Included
     end: usize,   // Excluded
+    loop_mode: LoopMode,
+    // 1.0 or -1.0, flipped by the ping-pong mode and the reverse modes.
+    direction: f32,
+    playing: bool,
+    gate: bool,
+    gate_gain: f32,
+    release_step: f32,
+    ended: bool,
+    crossfade: LoopCrossfade,
+    zero_crossing_snap: bool,
 }
 
 impl Samples {
@@ -18,28 +59,45 @@ impl Samples {
         let index = 0.0;
         let step = 1.0;
         let sample_rate = 44_100_f32;
+        let output_left = Rc::new(RefCell::new(Buffer::new()));
+        let output_right = Rc::new(RefCell::new(Buffer::new()));
         let output = Rc::new(RefCell::new(Buffer::new()));
-        let samples = vec![0.0, 0.0];
+        let samples = SampleData::from_mono(vec![0.0, 0.0]);
         let level = 1.0;
         let start = 0;
         let end = samples.len();
-        Self {
+        let mut wav = Self {
             index,
             step,
+            output_left,
+            output_right,
             output,
             sample_rate,
             samples,
             level,
             start,
             end,
-        }
+            loop_mode: LoopMode::Forward,
+            direction: 1.0,
+            // Free running until a gate/trigger API is used.
+            playing: true,
+            gate: true,
+            gate_gain: 1.0,
+            release_step: 0.0,
+            ended: false,
+            crossfade: LoopCrossfade::default(),
+            zero_crossing_snap: false,
+        };
+        wav.set_sample_rate(sample_rate);
+        wav
     }
 
-    pub fn load_samples(&mut self, samples: Vec<f32>) {
+    // Mono, stereo or more channels (see SampleData).
+    pub fn load_samples(&mut self, samples: SampleData) {
         self.samples = samples;
-        self.index = 0.0;
         self.start = 0;
         self.end = self.samples.len();
+        self.rewind();
     }
 
     pub fn set_level(&mut self, level: f32) {
@@ -47,60 +105,244 @@ impl Samples {
     }
 
     pub fn set_start(&mut self, start: usize) {
-        self.start = start;
+        self.start = self.snap(start.min(self.samples.len()));
+        if self.index < self.start as f32 {
+            self.index = self.start as f32
+        }
     }
 
     pub fn set_end(&mut self, end: usize) {
-        self.end = end;
-        if self.index >= end as f32 {
-            self.index = self.start as f32
+        self.end = self.snap(end.min(self.samples.len()));
+        if self.index >= self.last_index() {
+            self.index = self.first_index()
         }
     }
 
+    // Playback rate, negative values play backward.
     pub fn set_step(&mut self, step: f32) {
         self.step = step;
     }
 
-    fn next_index(&self) -> f32 {
-        let new_index = self.index + self.step;
-        // -1 because of the interpolation.
-        if new_index >= self.end as f32 - 1.0 {
-            self.start as f32
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) {
+        self.loop_mode = loop_mode;
+        self.direction = if loop_mode.is_reversed() { -1.0 } else { 1.0 };
+    }
+
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    // Crossfade at the loop point of the forward and reverse loops, length in samples (0: none).
+    pub fn set_loop_crossfade(&mut self, length: usize, curve: CrossfadeCurve) {
+        self.crossfade = LoopCrossfade::new(length, curve);
+    }
+
+    // Move the start and end points to the closest zero crossing when they are set.
+    pub fn set_zero_crossing_snap(&mut self, zero_crossing_snap: bool) {
+        self.zero_crossing_snap = zero_crossing_snap;
+    }
+
+    fn snap(&self, index: usize) -> usize {
+        if self.zero_crossing_snap {
+            find_zero_crossing(self.samples.channel(0), index, MAX_ZERO_CROSSING_DISTANCE)
+        } else {
+            index
+        }
+    }
+
+    // Restart from the beginning (the end for reverse modes) and play.
+    pub fn trigger(&mut self) {
+        self.rewind();
+        self.playing = true;
+        self.gate = true;
+        self.gate_gain = 1.0;
+    }
+
+    // Gate on triggers the sample, gate off fades it out.
+    pub fn set_gate(&mut self, gate: bool) {
+        if gate {
+            self.trigger();
         } else {
-            new_index
+            self.gate = false;
         }
     }
 
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    // True once after a one-shot playback reached its end (or a released gate faded out).
+    pub fn take_ended(&mut self) -> bool {
+        let ended = self.ended;
+        self.ended = false;
+        ended
+    }
+
     pub fn current_index(&self) -> f32 {
         self.index
     }
+
+    fn first_index(&self) -> f32 {
+        self.start as f32
+    }
+
+    // Last index that can be read: -1 because of the interpolation.
+    fn last_index(&self) -> f32 {
+        (self.end as f32 - 1.0).max(self.first_index())
+    }
+
+    fn rewind(&mut self) {
+        self.direction = if self.loop_mode.is_reversed() { -1.0 } else { 1.0 };
+        self.index = if self.direction * self.step < 0.0 {
+            self.last_index()
+        } else {
+            self.first_index()
+        };
+    }
+
+    fn stop(&mut self) {
+        self.playing = false;
+        self.ended = true;
+    }
+
+    fn next_index(&mut self) {
+        let (first, last) = (self.first_index(), self.last_index());
+        let length = last - first;
+        if length <= 0.0 {
+            self.index = first;
+            return;
+        }
+
+        let index = self.index + self.step * self.direction;
+        if self.loop_mode.is_one_shot() {
+            // The last index is played before stopping.
+            if index >= first && index <= last {
+                self.index = index;
+            } else {
+                self.index = index.clamp(first, last);
+                self.stop();
+            }
+            return;
+        }
+        if index >= first && index < last {
+            self.index = index;
+            return;
+        }
+
+        match self.loop_mode {
+            LoopMode::OneShot | LoopMode::Reverse => {}
+            LoopMode::Forward | LoopMode::ReverseLoop => {
+                // With a crossfade, the loop restarts after the faded in part.
+                let fade = self.crossfade.effective_length(length);
+                if index >= last {
+                    self.index = first + fade + (index - last).rem_euclid(length - fade);
+                } else {
+                    self.index = last - fade - (first - index).rem_euclid(length - fade);
+                }
+            }
+            LoopMode::PingPong => {
+                // Reflect on the bounds, the direction flips on each reflection.
+                let period = 2.0 * length;
+                let folded = (index - first).rem_euclid(period);
+                let flips = ((index - first) / length).floor().abs() as i64;
+                if folded > length {
+                    self.index = first + period - folded;
+                } else {
+                    self.index = first + folded;
+                }
+                if flips % 2 == 1 {
+                    self.direction = -self.direction;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn read(&self) -> (f32, f32) {
+        let value = self.read_at(self.index);
+        if !matches!(self.loop_mode, LoopMode::Forward | LoopMode::ReverseLoop) {
+            return value;
+        }
+        let (first, last) = (self.first_index(), self.last_index());
+        let fade = self.crossfade.effective_length(last - first);
+        if fade < 1.0 {
+            return value;
+        }
+
+        // Blend with the part of the loop played after the wrap.
+        if self.step * self.direction >= 0.0 && self.index >= last - fade {
+            let t = (self.index - (last - fade)) / fade;
+            let (fade_out, fade_in) = self.crossfade.gains(t);
+            let other = self.read_at(first + (self.index - (last - fade)));
+            (fade_out * value.0 + fade_in * other.0, fade_out * value.1 + fade_in * other.1)
+        } else if self.step * self.direction < 0.0 && self.index < first + fade {
+            let t = (first + fade - self.index) / fade;
+            let (fade_out, fade_in) = self.crossfade.gains(t);
+            let other = self.read_at(last - (first + fade - self.index));
+            (fade_out * value.0 + fade_in * other.0, fade_out * value.1 + fade_in * other.1)
+        } else {
+            value
+        }
+    }
+
+    #[inline]
+    fn read_at(&self, index: f32) -> (f32, f32) {
+        let i = index as usize;
+        if i + 1 < self.end {
+            self.samples.interpolated_frame(index)
+        } else {
+            self.samples.frame(i)
+        }
+    }
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Module for Samples {
     fn process(&mut self) {
-        let mut wrapped_buf = self.output.try_borrow_mut().unwrap();
-        let buf = wrapped_buf.get_mut();
-
-        for b in buf {
-            let i = self.index as usize;
-            let w = self.index.fract();
+        let (output_left, output_right) = (self.output_left.clone(), self.output_right.clone());
+        let output = self.output.clone();
+        let mut output_left = output_left.try_borrow_mut().unwrap();
+        let mut output_right = output_right.try_borrow_mut().unwrap();
+        let mut output = output.try_borrow_mut().unwrap();
+        let outputs = output_left
+            .get_mut()
+            .iter_mut()
+            .zip(output_right.get_mut())
+            .zip(output.get_mut());
 
-            let v0 = self.samples[i];
-            let v1 = self.samples[i + 1];
+        for ((out_l, out_r), out) in outputs {
+            if !self.playing {
+                *out_l = 0.0;
+                *out_r = 0.0;
+                *out = 0.0;
+                continue;
+            }
 
-            *b = self.level * ((1.0 - w) * v0 + w * v1);
-            self.index = self.next_index();
+            if !self.gate {
+                self.gate_gain -= self.release_step;
+                if self.gate_gain <= 0.0 {
+                    self.gate_gain = 0.0;
+                    self.stop();
+                }
+            }
 
-            // self.index += self.step;
-            // // -1 because of the interpolation.
-            // if self.index >= self.end as f32 - 1.0 {
-            //     self.index = self.start as f32
-            // }
+            let gain = self.level * self.gate_gain;
+            let (left, right) = self.read();
+            *out_l = gain * left;
+            *out_r = gain * right;
+            *out = gain * 0.5 * (left + right);
+            self.next_index();
         }
     }
 
     fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        self.release_step = 1000.0 / (RELEASE_MS * sample_rate);
     }
 }
 
@@ -112,10 +354,10 @@ impl MonoGenerator for Samples {
 
 impl StereoGenerator for Samples {
     fn get_left_output(&self) -> SharedBuffer {
-        self.output.clone()
+        self.output_left.clone()
     }
 
     fn get_right_output(&self) -> SharedBuffer {
-        self.output.clone()
+        self.output_right.clone()
     }
 }
@@ -0,0 +1,96 @@
+use crate::core::{time_constant_coef, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+const INTEGRATION_TIME_MS: f32 = 300.0;
+
+// Pass through module measuring the phase correlation: 1 mono, 0 uncorrelated, -1 out of phase.
+pub struct CorrelationMeter {
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    coef: f32,
+    lr: f32,
+    ll: f32,
+    rr: f32,
+    correlation: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new() -> Self {
+        Self {
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            coef: time_constant_coef(INTEGRATION_TIME_MS, 44_100.0),
+            lr: 0.0,
+            ll: 0.0,
+            rr: 0.0,
+            correlation: 0.0,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // Updated on each chunk, -1..1
+    pub fn correlation(&self) -> f32 {
+        self.correlation
+    }
+}
+
+impl Default for CorrelationMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for CorrelationMeter {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.coef = time_constant_coef(INTEGRATION_TIME_MS, sample_rate);
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+        output_left.get_mut().copy_from_slice(input_left.get());
+        output_right.get_mut().copy_from_slice(input_right.get());
+
+        let c = self.coef;
+        for (l, r) in input_left.get().iter().zip(input_right.get()) {
+            self.lr = l * r + c * (self.lr - l * r);
+            self.ll = l * l + c * (self.ll - l * l);
+            self.rr = r * r + c * (self.rr - r * r);
+        }
+        let energy = (self.ll * self.rr).sqrt();
+        let correlation = if energy > 1e-12 { self.lr / energy } else { 0.0 };
+        self.correlation = correlation.clamp(-1.0, 1.0);
+    }
+}
+
+impl StereoGenerator for CorrelationMeter {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for CorrelationMeter {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        CorrelationMeter::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        CorrelationMeter::set_right_input(self, input)
+    }
+}
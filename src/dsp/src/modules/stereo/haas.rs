@@ -0,0 +1,112 @@
+use crate::core::{DelayLine, Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+const MAX_HAAS_DELAY_MS: f32 = 40.0;
+
+// Widens by delaying one side by a few milliseconds (precedence effect).
+pub struct HaasWidener {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    line: DelayLine,
+    delay_ms: f32,
+    delay_right: bool,
+    mix: f32,
+}
+
+impl HaasWidener {
+    pub fn new() -> Self {
+        let sample_rate = 44_100.0;
+        Self {
+            sample_rate,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            line: DelayLine::new((MAX_HAAS_DELAY_MS * sample_rate / 1000.0) as usize),
+            delay_ms: 12.0,
+            delay_right: true,
+            mix: 1.0,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // 0..40 ms
+    pub fn set_delay(&mut self, delay_ms: f32) {
+        self.delay_ms = delay_ms.clamp(0.0, MAX_HAAS_DELAY_MS);
+    }
+
+    // Delayed side: true for right, false for left.
+    pub fn set_delay_right(&mut self, delay_right: bool) {
+        if self.delay_right != delay_right {
+            self.delay_right = delay_right;
+            self.line.clear();
+        }
+    }
+
+    // Amount of delayed signal on the delayed side, 0..1
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Default for HaasWidener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for HaasWidener {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.line
+            .resize((MAX_HAAS_DELAY_MS * sample_rate / 1000.0) as usize);
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+
+        let delay = self.delay_ms * self.sample_rate / 1000.0;
+        let (direct_in, delayed_in, direct_out, delayed_out) = if self.delay_right {
+            (&input_left, &input_right, &mut output_left, &mut output_right)
+        } else {
+            (&input_right, &input_left, &mut output_right, &mut output_left)
+        };
+        direct_out.get_mut().copy_from_slice(direct_in.get());
+        for (out, x) in delayed_out.get_mut().iter_mut().zip(delayed_in.get()) {
+            self.line.push(*x);
+            *out = (1.0 - self.mix) * x + self.mix * self.line.read_interpolated(delay);
+        }
+    }
+}
+
+impl StereoGenerator for HaasWidener {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for HaasWidener {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        HaasWidener::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        HaasWidener::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,135 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::core::{
+    Biquad, BiquadCoefs, BiquadType, Module, SharedBuffer, StereoGenerator, StereoProcessor,
+};
+use crate::modules::oscillators::get_pan;
+
+// Width, bass mono and balance, processed in mid/side.
+pub struct StereoImager {
+    sample_rate: f32,
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+    width: f32,
+    bass_mono_frequency: Option<f32>,
+    // Linkwitz-Riley high pass on the side signal
+    side_filters: [Biquad; 2],
+    balance_left: f32,
+    balance_right: f32,
+}
+
+impl StereoImager {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+            width: 1.0,
+            bass_mono_frequency: None,
+            side_filters: [Biquad::default(); 2],
+            balance_left: 1.0,
+            balance_right: 1.0,
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    // 0: mono, 1: unchanged, 2: side doubled
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    // Removes the side content below the crossover frequency, None to disable.
+    pub fn set_bass_mono(&mut self, frequency: Option<f32>) {
+        self.bass_mono_frequency = frequency;
+        self.update_filters();
+    }
+
+    // -1 (left) .. 1 (right), unity at center.
+    pub fn set_balance(&mut self, balance: f32) {
+        // get_pan has the left side at 1.
+        let (left, right) = get_pan(-balance.clamp(-1.0, 1.0));
+        let max = left.max(right);
+        self.balance_left = left / max;
+        self.balance_right = right / max;
+    }
+
+    fn update_filters(&mut self) {
+        if let Some(frequency) = self.bass_mono_frequency {
+            let coefs = BiquadCoefs::new(
+                BiquadType::HighPass,
+                self.sample_rate,
+                frequency,
+                FRAC_1_SQRT_2,
+                0.0,
+            );
+            self.side_filters.iter_mut().for_each(|f| f.set_coefs(coefs));
+        }
+    }
+}
+
+impl Default for StereoImager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for StereoImager {
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_filters();
+    }
+
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+
+        let bass_mono = self.bass_mono_frequency.is_some();
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for ((in_l, in_r), (out_l, out_r)) in inputs.zip(outputs) {
+            let mid = (in_l + in_r) / 2.0;
+            let mut side = self.width * (in_l - in_r) / 2.0;
+            if bass_mono {
+                side = self
+                    .side_filters
+                    .iter_mut()
+                    .fold(side, |acc, f| f.process(acc));
+            }
+            *out_l = self.balance_left * (mid + side);
+            *out_r = self.balance_right * (mid - side);
+        }
+    }
+}
+
+impl StereoGenerator for StereoImager {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for StereoImager {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        StereoImager::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        StereoImager::set_right_input(self, input)
+    }
+}
@@ -0,0 +1,147 @@
+use crate::core::{Module, SharedBuffer, StereoGenerator, StereoProcessor};
+
+// Left/right -> mid (left output) / side (right output)
+pub struct MidSideEncoder {
+    input_left: SharedBuffer,
+    input_right: SharedBuffer,
+    output_mid: SharedBuffer,
+    output_side: SharedBuffer,
+}
+
+impl MidSideEncoder {
+    pub fn new() -> Self {
+        Self {
+            input_left: SharedBuffer::default(),
+            input_right: SharedBuffer::default(),
+            output_mid: SharedBuffer::default(),
+            output_side: SharedBuffer::default(),
+        }
+    }
+
+    pub fn set_left_input(&mut self, input: SharedBuffer) {
+        self.input_left = input;
+    }
+
+    pub fn set_right_input(&mut self, input: SharedBuffer) {
+        self.input_right = input;
+    }
+
+    pub fn get_mid_output(&self) -> SharedBuffer {
+        self.output_mid.clone()
+    }
+
+    pub fn get_side_output(&self) -> SharedBuffer {
+        self.output_side.clone()
+    }
+}
+
+impl Default for MidSideEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for MidSideEncoder {
+    fn process(&mut self) {
+        let input_left = self.input_left.try_borrow().unwrap();
+        let input_right = self.input_right.try_borrow().unwrap();
+        let mut output_mid = self.output_mid.try_borrow_mut().unwrap();
+        let mut output_side = self.output_side.try_borrow_mut().unwrap();
+
+        let inputs = input_left.get().iter().zip(input_right.get());
+        let outputs = output_mid.get_mut().iter_mut().zip(output_side.get_mut());
+        for ((l, r), (m, s)) in inputs.zip(outputs) {
+            *m = (l + r) / 2.0;
+            *s = (l - r) / 2.0;
+        }
+    }
+}
+
+impl StereoGenerator for MidSideEncoder {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_mid.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_side.clone()
+    }
+}
+
+impl StereoProcessor for MidSideEncoder {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        MidSideEncoder::set_left_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        MidSideEncoder::set_right_input(self, input)
+    }
+}
+
+// Mid (left input) / side (right input) -> left/right
+pub struct MidSideDecoder {
+    input_mid: SharedBuffer,
+    input_side: SharedBuffer,
+    output_left: SharedBuffer,
+    output_right: SharedBuffer,
+}
+
+impl MidSideDecoder {
+    pub fn new() -> Self {
+        Self {
+            input_mid: SharedBuffer::default(),
+            input_side: SharedBuffer::default(),
+            output_left: SharedBuffer::default(),
+            output_right: SharedBuffer::default(),
+        }
+    }
+
+    pub fn set_mid_input(&mut self, input: SharedBuffer) {
+        self.input_mid = input;
+    }
+
+    pub fn set_side_input(&mut self, input: SharedBuffer) {
+        self.input_side = input;
+    }
+}
+
+impl Default for MidSideDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for MidSideDecoder {
+    fn process(&mut self) {
+        let input_mid = self.input_mid.try_borrow().unwrap();
+        let input_side = self.input_side.try_borrow().unwrap();
+        let mut output_left = self.output_left.try_borrow_mut().unwrap();
+        let mut output_right = self.output_right.try_borrow_mut().unwrap();
+
+        let inputs = input_mid.get().iter().zip(input_side.get());
+        let outputs = output_left.get_mut().iter_mut().zip(output_right.get_mut());
+        for ((m, s), (l, r)) in inputs.zip(outputs) {
+            *l = m + s;
+            *r = m - s;
+        }
+    }
+}
+
+impl StereoGenerator for MidSideDecoder {
+    fn get_left_output(&self) -> SharedBuffer {
+        self.output_left.clone()
+    }
+
+    fn get_right_output(&self) -> SharedBuffer {
+        self.output_right.clone()
+    }
+}
+
+impl StereoProcessor for MidSideDecoder {
+    fn set_left_input(&mut self, input: SharedBuffer) {
+        MidSideDecoder::set_mid_input(self, input)
+    }
+
+    fn set_right_input(&mut self, input: SharedBuffer) {
+        MidSideDecoder::set_side_input(self, input)
+    }
+}
@@ -0,0 +1,9 @@
+mod correlation;
+mod haas;
+mod imager;
+mod mid_side;
+
+pub use correlation::*;
+pub use haas::*;
+pub use imager::*;
+pub use mid_side::*;
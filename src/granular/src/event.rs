@@ -1,20 +1,51 @@
 use std::time::Duration;
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
+use dsp::{core::{CrossfadeCurve, SampleData}, modules::oscillators::{GrainScheduling, GrainWindow, PitchQuantization, RegionEdge, ScanMode, StealingPolicy}};
 use strum_macros::Display;
 
 #[derive(Display)]
 pub enum SynthEvent {
-    LoadSound(Vec<f32>),
+    // Mixed down to stereo, shared by the engines.
+    LoadSound(SampleData),
     MainLevel(f32),
     Start(usize),
     End(usize),
     Step(f32),
     PanSpread(f32),
     ScanSpread(f32),
+    ScanMode(ScanMode),
+    ScanPosition(f32),
     GrainStep(f32),
     GrainsPerSec(f32),
+    GrainScheduling(GrainScheduling),
+    GrainJitter(f32),
     GrainEnvelop(f32,f32,f32),
+    GrainWindow(GrainWindow),
+    GrainPoolSize(usize),
+    StealingPolicy(StealingPolicy),
+    GrainPitchSpread(f32, PitchQuantization),
+    GrainGainSpread(f32),
+    GrainDurationSpread(f32),
+    GrainReverseProbability(f32),
+    SpectralMode(bool),
+    SpectralFreeze(bool),
+    SpectralCapture,
+    SpectralPhaseRandomization(f32),
+    SpectralBinDensity(f32),
+    SpectralBlur(f32),
+    SpectralSmear(f32),
+    VocoderMode(bool),
+    VocoderTimeRatio(f32),
+    VocoderPitchRatio(f32),
+    VocoderPhaseLocking(bool),
+    // Preservation, threshold
+    VocoderTransients(bool, f32),
+    LoopCrossfade(usize, CrossfadeCurve),
+    ZeroCrossingSnap(bool),
+    RegionEdge(RegionEdge),
+    ChannelSwap(f32),
+    BalanceSpread(f32),
 }
 
 #[derive(Clone)]
@@ -51,6 +82,9 @@ pub fn create_synth_event_sender_receiver() -> (SynthEventSender, SynthEventRece
 pub enum GuiEvent {
     SampleRms(Vec<f32>),
     Position(f32),
+    Spectrum(Vec<f32>),
+    ActiveGrains(usize),
+    Correlation(f32),
 }
 
 #[derive(Clone)]
@@ -4,6 +4,10 @@ use std::{
 };
 
 use crate::GuiEvent;
+use dsp::core::{load_wav_file, CrossfadeCurve, SampleData};
+use dsp::modules::oscillators::{
+    GrainScheduling, GrainWindow, PitchQuantization, RegionEdge, ScanMode, StealingPolicy, MAX_GRAINS,
+};
 use crate::{GranularError, GuiEventSender, SynthEvent, SynthEventSender, SynthState};
 use ring_channel::RingReceiver;
 
@@ -15,7 +19,7 @@ pub struct GranularController {
     synth_event_sender: SynthEventSender,
     gui_event_sender: Option<GuiEventSender>,
     current_file: Option<String>,
-    samples: Vec<f32>,
+    samples: SampleData,
     sample_rate: f32,
     main_level: f32,
     start: f32,
@@ -34,7 +38,7 @@ impl GranularController {
         sample_rate: f32,
     ) -> Self {
         let current_file = None;
-        let samples = vec![];
+        let samples = SampleData::default();
         let main_level = 1.0;
         let start = 0.0;
         let end = 0.0;
@@ -106,6 +110,17 @@ impl GranularController {
 
     }
 
+    // Forward, reverse, freeze, random walk, random jumps or manual.
+    pub fn set_scan_mode(&mut self, scan_mode: ScanMode) {
+        self.synth_event_sender.send(SynthEvent::ScanMode(scan_mode));
+    }
+
+    // Position of the scan head in manual mode, 0..1 of the sample bounds
+    pub fn set_scan_position(&mut self, position: f32) {
+        let position = position.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::ScanPosition(position));
+    }
+
     fn update_grain_env(&mut self){
         let attack_duration = self.grain_duration * self.grain_attack_ratio;
         let release_duration = self.grain_duration * self.grain_release_ratio;
@@ -126,6 +141,46 @@ impl GranularController {
         self.update_grain_env();
     }
 
+    // Envelope shape of the grains (the trapezoid follows the attack/release ratios).
+    pub fn set_grain_window(&mut self, window: GrainWindow) {
+        self.synth_event_sender.send(SynthEvent::GrainWindow(window));
+    }
+
+    // Maximum number of grains playing at once
+    pub fn set_grain_pool_size(&mut self, pool_size: usize) {
+        let pool_size = pool_size.clamp(1, MAX_GRAINS);
+        self.synth_event_sender.send(SynthEvent::GrainPoolSize(pool_size));
+    }
+
+    // New grains when the pool is full: wait, or steal the oldest or quietest grain.
+    pub fn set_stealing_policy(&mut self, policy: StealingPolicy) {
+        self.synth_event_sender.send(SynthEvent::StealingPolicy(policy));
+    }
+
+    // Random pitch offset of each grain, in -semi_tones..semi_tones
+    pub fn set_grain_pitch_spread(&mut self, semi_tones: f32, quantization: PitchQuantization) {
+        let semi_tones = semi_tones.clamp(0.0, 48.0);
+        self.synth_event_sender.send(SynthEvent::GrainPitchSpread(semi_tones, quantization));
+    }
+
+    // Random attenuation of each grain, 0..1
+    pub fn set_grain_gain_spread(&mut self, spread: f32) {
+        let spread = spread.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::GrainGainSpread(spread));
+    }
+
+    // Random duration of each grain (1 +/- spread), 0..1
+    pub fn set_grain_duration_spread(&mut self, spread: f32) {
+        let spread = spread.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::GrainDurationSpread(spread));
+    }
+
+    // Probability for a grain to play backward, 0..1
+    pub fn set_grain_reverse_probability(&mut self, probability: f32) {
+        let probability = probability.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::GrainReverseProbability(probability));
+    }
+
     pub fn set_grain_tune(&mut self, semi_tones: f32) {
         self.grain_semi_tones = semi_tones;
         let ratio = 2.0_f32.powf(semi_tones / 12.0);
@@ -137,73 +192,146 @@ impl GranularController {
         self.synth_event_sender.send(SynthEvent::GrainsPerSec(grains_per_sec));
     }
 
+    // Synchronous, asynchronous (Poisson) or bursts, at the grains per sec density.
+    pub fn set_grain_scheduling(&mut self, scheduling: GrainScheduling) {
+        self.synth_event_sender.send(SynthEvent::GrainScheduling(scheduling));
+    }
+
+    // Random deviation of the intervals between grains, in % of the interval
+    pub fn set_grain_jitter(&mut self, jitter_percent: f32) {
+        let jitter = (jitter_percent / 100.0).clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::GrainJitter(jitter));
+    }
+
+    // Play the spectrum at the scan position instead of the grains.
+    pub fn set_spectral_mode(&mut self, spectral_mode: bool) {
+        self.synth_event_sender.send(SynthEvent::SpectralMode(spectral_mode));
+    }
+
+    pub fn set_spectral_freeze(&mut self, frozen: bool) {
+        self.synth_event_sender.send(SynthEvent::SpectralFreeze(frozen));
+    }
+
+    // Capture a new frame at the scan position while frozen.
+    pub fn spectral_capture(&mut self) {
+        self.synth_event_sender.send(SynthEvent::SpectralCapture);
+    }
+
+    pub fn set_spectral_phase_randomization(&mut self, amount: f32) {
+        let amount = amount.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::SpectralPhaseRandomization(amount));
+    }
+
+    pub fn set_spectral_bin_density(&mut self, density: f32) {
+        let density = density.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::SpectralBinDensity(density));
+    }
+
+    pub fn set_spectral_blur(&mut self, blur: f32) {
+        let blur = blur.clamp(0.0, 0.99);
+        self.synth_event_sender.send(SynthEvent::SpectralBlur(blur));
+    }
+
+    pub fn set_spectral_smear(&mut self, smear: f32) {
+        let smear = smear.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::SpectralSmear(smear));
+    }
+
+    // Play the sample through the phase vocoder instead of the grains.
+    pub fn set_vocoder_mode(&mut self, vocoder_mode: bool) {
+        self.synth_event_sender.send(SynthEvent::VocoderMode(vocoder_mode));
+    }
+
+    // Duration ratio of the vocoder, 2.0 plays twice as slow (0.1..10)
+    pub fn set_vocoder_time_ratio(&mut self, ratio: f32) {
+        let ratio = ratio.clamp(0.1, 10.0);
+        self.synth_event_sender.send(SynthEvent::VocoderTimeRatio(ratio));
+    }
+
+    // Pitch of the vocoder, -24..24 semi tones
+    pub fn set_vocoder_tune(&mut self, semi_tones: f32) {
+        let ratio = 2.0_f32.powf(semi_tones.clamp(-24.0, 24.0) / 12.0);
+        self.synth_event_sender.send(SynthEvent::VocoderPitchRatio(ratio));
+    }
+
+    pub fn set_vocoder_phase_locking(&mut self, phase_locking: bool) {
+        self.synth_event_sender.send(SynthEvent::VocoderPhaseLocking(phase_locking));
+    }
+
+    // Onsets above the threshold (0..1) reset the phases, keeping the attacks sharp.
+    pub fn set_vocoder_transients(&mut self, preservation: bool, threshold: f32) {
+        let threshold = threshold.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::VocoderTransients(preservation, threshold));
+    }
+
+    // Crossfade of the scan head at the loop point, in ms.
+    pub fn set_loop_crossfade(&mut self, length_ms: f32, equal_power: bool) {
+        let length = (length_ms.max(0.0) * self.sample_rate / 1000.0) as usize;
+        let curve = if equal_power {
+            CrossfadeCurve::EqualPower
+        } else {
+            CrossfadeCurve::Linear
+        };
+        self.synth_event_sender.send(SynthEvent::LoopCrossfade(length, curve));
+    }
+
+    // Snap the sample bounds to the closest zero crossing.
+    pub fn set_zero_crossing_snap(&mut self, zero_crossing_snap: bool) {
+        self.synth_event_sender.send(SynthEvent::ZeroCrossingSnap(zero_crossing_snap));
+    }
+
+    // Grains at the sample bounds: clamped, wrapped or mirrored.
+    pub fn set_region_edge(&mut self, edge: RegionEdge) {
+        self.synth_event_sender.send(SynthEvent::RegionEdge(edge));
+    }
+
+    // Probability for a grain to read the sample channels swapped, 0..1
+    pub fn set_channel_swap(&mut self, probability: f32) {
+        let probability = probability.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::ChannelSwap(probability));
+    }
+
+    // Random balance of each grain, 0..1
+    pub fn set_balance_spread(&mut self, spread: f32) {
+        let spread = spread.clamp(0.0, 1.0);
+        self.synth_event_sender.send(SynthEvent::BalanceSpread(spread));
+    }
+
+    // The samples replaced in the synth come with the state: they are freed here.
     pub fn update_synth_state(&mut self, state: SynthState) {
         if let Some(gui_sender) = &self.gui_event_sender {
             //println!("POSITION: {}/{}",self.samples.len() as f32,state.index);
 
             gui_sender.send(GuiEvent::Position(state.index / self.samples.len() as f32));
+            if let Some(spectrum) = state.spectrum {
+                gui_sender.send(GuiEvent::Spectrum(spectrum));
+            }
+            gui_sender.send(GuiEvent::ActiveGrains(state.active_grains));
+            gui_sender.send(GuiEvent::Correlation(state.correlation));
         }
     }
 
     pub fn load_samples(&mut self, file_name: String) -> Result<(), GranularError> {
         // Try to load samples
         self.current_file = Some(file_name.to_owned());
-        let mut reader = hound::WavReader::open(&file_name)
+        let channels = load_wav_file(&file_name, self.sample_rate)
             .map_err(|_e| GranularError::FailedToLoadSampleFile(file_name))?;
-        let spec = reader.spec();
-        println!("Channels: {}", spec.channels);
-        println!("Sample rate: {}", spec.sample_rate);
-        println!("Bits_per_sample: {}", spec.bits_per_sample);
-        let original_sample_rate = spec.sample_rate as f32;
-
-        // Get first channel and convert data -> -1<f32<1
-        // Actually load only the fisrt channel.
-        let max_value = 2.0_f32.powi(spec.bits_per_sample as i32);
-        let original_data: Vec<f32> = reader
-            .samples::<i32>()
-            .map(|e| e.unwrap())
-            .step_by(spec.channels as usize)
-            .map(|v| (v as f32) / max_value)
-            .collect();
-        let ratio = self.sample_rate / original_sample_rate;
-        let original_wav_len = original_data.len() as f32;
-
-        // Resample
-        self.samples.truncate(0);
-        if ratio < 1.0 {
-            // Down sampling
-            let new_size = (ratio * original_wav_len).round() as usize;
-            for target_idx in 0..new_size {
-                let source_idx = (target_idx as f32 / ratio).round() as usize;
-                self.samples.push(original_data[source_idx]);
-            }
-        } else {
-            // Upper sampling
-            let new_size = (ratio * original_wav_len).round() as usize;
-            for target_idx in 0..new_size {
-                let source_idx = (target_idx as f32 / ratio).trunc() as usize;
-                let coef = (target_idx as f32 / ratio).fract();
-                if source_idx + 1 >= original_wav_len as usize {
-                    break;
-                }
-                self.samples.push(
-                    (1.0 - coef) * original_data[source_idx] + original_data[source_idx + 1] * coef,
-                );
-            }
-        }
 
-        // Send data to Synth
-        let vec_to_send_to_synth: Vec<f32> = self.samples.iter().copied().collect();
-        self.synth_event_sender
-            .send(SynthEvent::LoadSound(vec_to_send_to_synth));
+        self.samples = SampleData::new(channels);
+
+        // Send data to Synth, ready to play: the engines do not allocate on the audio thread.
+        // The previous samples come back with the synth state (see update_synth_state).
+        let samples = self.samples.clone().into_stereo();
+        self.synth_event_sender.send(SynthEvent::LoadSound(samples));
 
-        // Send data to gui
+        // Send data to gui (channels mixed)
         if let Some(gui_sender) = &self.gui_event_sender {
             let window_size = self.samples.len() / 600;
             let vec_to_send_to_gui: Vec<f32> = if window_size <= 1 {
                 vec![0.0]
             } else {
-                let w = self.samples.chunks(window_size);
+                let mono = self.samples.to_mono();
+                let w = mono.chunks(window_size);
                 let rms: Vec<f32> = w
                     .into_iter()
                     .map(|w| w.iter().map(|v| *v * *v).sum::<f32>().sqrt())
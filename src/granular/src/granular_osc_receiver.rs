@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use dsp::core::OscMessageHandler;
+use dsp::modules::oscillators::{GrainScheduling, GrainWindow, PitchQuantization, RegionEdge, ScanMode, StealingPolicy};
 use rosc::{OscMessage, OscType};
 
 use crate::GranularController;
@@ -53,6 +54,23 @@ impl OscMessageHandler for GranularOscMessageHandler {
                 println!("Change scan spread: {}", spread);
                 ctrl.set_scan_spread(*spread);
             }
+            // name, optional parameter (random walk size in samples, jump interval in seconds)
+            ("/scan_mode", [OscType::String(name), parameter @ ..]) => {
+                let parameter = match parameter {
+                    [OscType::Float(p)] => *p,
+                    _ => 1.0,
+                };
+                match ScanMode::from_name(name, parameter) {
+                    Some(scan_mode) => {
+                        println!("Change scan mode: {:?}", scan_mode);
+                        ctrl.set_scan_mode(scan_mode);
+                    }
+                    None => println!("Unknown scan mode: {}", name),
+                }
+            }
+            ("/scan_position", [OscType::Float(position)]) => {
+                ctrl.set_scan_position(*position);
+            }
             ("/grain_tune", [OscType::Float(tune)]) => {
                 println!("Change grain tune: {}", tune);
                 ctrl.set_grain_tune(*tune);
@@ -69,6 +87,151 @@ impl OscMessageHandler for GranularOscMessageHandler {
                 println!("Change grain_env: {} / {}", attack, release);
                 ctrl.set_grain_attack_release_ratio(*attack,*release);
             }
+            ("/spectral_mode", [OscType::Float(on)]) => {
+                println!("Change spectral mode: {}", on);
+                ctrl.set_spectral_mode(*on > 0.5);
+            }
+            ("/spectral_freeze", [OscType::Float(on)]) => {
+                println!("Change spectral freeze: {}", on);
+                ctrl.set_spectral_freeze(*on > 0.5);
+            }
+            ("/spectral_capture", []) => {
+                println!("Spectral capture");
+                ctrl.spectral_capture();
+            }
+            ("/spectral_phase_random", [OscType::Float(amount)]) => {
+                println!("Change spectral phase randomization: {}", amount);
+                ctrl.set_spectral_phase_randomization(*amount);
+            }
+            ("/spectral_density", [OscType::Float(density)]) => {
+                println!("Change spectral bin density: {}", density);
+                ctrl.set_spectral_bin_density(*density);
+            }
+            ("/spectral_blur", [OscType::Float(blur)]) => {
+                println!("Change spectral blur: {}", blur);
+                ctrl.set_spectral_blur(*blur);
+            }
+            ("/spectral_smear", [OscType::Float(smear)]) => {
+                println!("Change spectral smear: {}", smear);
+                ctrl.set_spectral_smear(*smear);
+            }
+            ("/vocoder_mode", [OscType::Float(on)]) => {
+                println!("Change vocoder mode: {}", on);
+                ctrl.set_vocoder_mode(*on > 0.5);
+            }
+            ("/vocoder_time", [OscType::Float(ratio)]) => {
+                println!("Change vocoder time ratio: {}", ratio);
+                ctrl.set_vocoder_time_ratio(*ratio);
+            }
+            ("/vocoder_tune", [OscType::Float(semi_tones)]) => {
+                println!("Change vocoder tune: {}", semi_tones);
+                ctrl.set_vocoder_tune(*semi_tones);
+            }
+            ("/vocoder_phase_locking", [OscType::Float(on)]) => {
+                println!("Change vocoder phase locking: {}", on);
+                ctrl.set_vocoder_phase_locking(*on > 0.5);
+            }
+            ("/vocoder_transients", [OscType::Float(on), OscType::Float(threshold)]) => {
+                println!("Change vocoder transients: {} / {}", on, threshold);
+                ctrl.set_vocoder_transients(*on > 0.5, *threshold);
+            }
+            ("/loop_crossfade", [OscType::Float(length_ms), OscType::Float(equal_power)]) => {
+                println!("Change loop crossfade: {} / {}", length_ms, equal_power);
+                ctrl.set_loop_crossfade(*length_ms, *equal_power > 0.5);
+            }
+            ("/zero_crossing_snap", [OscType::Float(on)]) => {
+                println!("Change zero crossing snap: {}", on);
+                ctrl.set_zero_crossing_snap(*on > 0.5);
+            }
+            ("/channel_swap", [OscType::Float(probability)]) => {
+                println!("Change channel swap: {}", probability);
+                ctrl.set_channel_swap(*probability);
+            }
+            ("/region_edge", [OscType::String(name)]) => {
+                match RegionEdge::from_name(name) {
+                    Some(edge) => {
+                        println!("Change region edge: {:?}", edge);
+                        ctrl.set_region_edge(edge);
+                    }
+                    None => println!("Unknown region edge: {}", name),
+                }
+            }
+            ("/balance_spread", [OscType::Float(spread)]) => {
+                println!("Change balance spread: {}", spread);
+                ctrl.set_balance_spread(*spread);
+            }
+            ("/grain_pool_size", [OscType::Float(pool_size)]) => {
+                println!("Change grain pool size: {}", pool_size);
+                ctrl.set_grain_pool_size(pool_size.max(1.0) as usize);
+            }
+            ("/stealing_policy", [OscType::String(name)]) => {
+                match StealingPolicy::from_name(name) {
+                    Some(policy) => {
+                        println!("Change stealing policy: {:?}", policy);
+                        ctrl.set_stealing_policy(policy);
+                    }
+                    None => println!("Unknown stealing policy: {}", name),
+                }
+            }
+            // semi tones, optional quantization name
+            ("/grain_pitch_spread", [OscType::Float(semi_tones), quantization @ ..]) => {
+                let quantization = match quantization {
+                    [OscType::String(name)] => PitchQuantization::from_name(name),
+                    _ => Some(PitchQuantization::Free),
+                };
+                match quantization {
+                    Some(quantization) => {
+                        println!("Change grain pitch spread: {} {:?}", semi_tones, quantization);
+                        ctrl.set_grain_pitch_spread(*semi_tones, quantization);
+                    }
+                    None => println!("Unknown pitch quantization: {:?}", message.args),
+                }
+            }
+            ("/grain_gain_spread", [OscType::Float(spread)]) => {
+                println!("Change grain gain spread: {}", spread);
+                ctrl.set_grain_gain_spread(*spread);
+            }
+            ("/grain_duration_spread", [OscType::Float(spread)]) => {
+                println!("Change grain duration spread: {}", spread);
+                ctrl.set_grain_duration_spread(*spread);
+            }
+            ("/grain_reverse", [OscType::Float(probability)]) => {
+                println!("Change grain reverse probability: {}", probability);
+                ctrl.set_grain_reverse_probability(*probability);
+            }
+            // name, optional burst size
+            ("/grain_scheduling", [OscType::String(name), burst_size @ ..]) => {
+                let burst_size = match burst_size {
+                    [OscType::Float(size)] => *size as usize,
+                    [OscType::Int(size)] => (*size).max(0) as usize,
+                    _ => 4,
+                };
+                match GrainScheduling::from_name(name, burst_size) {
+                    Some(scheduling) => {
+                        println!("Change grain scheduling: {:?}", scheduling);
+                        ctrl.set_grain_scheduling(scheduling);
+                    }
+                    None => println!("Unknown grain scheduling: {}", name),
+                }
+            }
+            ("/grain_jitter", [OscType::Float(jitter)]) => {
+                println!("Change grain jitter: {}", jitter);
+                ctrl.set_grain_jitter(*jitter);
+            }
+            // name, optional parameter (tukey taper 0..1, curved slope -4..4)
+            ("/grain_window", [OscType::String(name), parameter @ ..]) => {
+                let parameter = match parameter {
+                    [OscType::Float(p)] => *p,
+                    _ => 0.5,
+                };
+                match GrainWindow::from_name(name, parameter) {
+                    Some(window) => {
+                        println!("Change grain window: {:?}", window);
+                        ctrl.set_grain_window(window);
+                    }
+                    None => println!("Unknown grain window: {}", name),
+                }
+            }
             _ => {
                 println!(
                     "No match for OSC address: {}, OSC arguments: {:?}",
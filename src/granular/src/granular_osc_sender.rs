@@ -20,6 +20,21 @@ pub enum GranularOscSenderError {
     NotAValidAddress(String),
 }
 
+// Values sent as a string: [[0,v0],[1,v1],...]
+fn encode_values(addr: &str, values: Vec<f32>) -> Vec<u8> {
+    let converted: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("[{},{}]", i, v))
+        .collect();
+    let payload = format!("[{}]", converted.join(","));
+    encoder::encode(&OscPacket::Message(OscMessage {
+        addr: addr.to_string(),
+        args: vec![OscType::String(payload)],
+    }))
+    .unwrap()
+}
+
 pub struct GranularOscMessageSender {
     gui_event_receiver: GuiEventReceiver,
     command_sender: Sender<GranularOscMessageSenderCommand>,
@@ -104,26 +119,36 @@ impl GranularOscMessageSender {
                         if let Some(e) = gui_recv.receive() {
                             match e {
                                 GuiEvent::SampleRms(data) => {
-                                    let converted: Vec<String> = data
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, v)| format!("[{},{}]", i, v))
-                                        .collect();
-                                    let payload = format!("[{}]", converted.join(","));
+                                    let msg_buf = encode_values("/rms", data);
+                                    sock.send_to(&msg_buf, remote_addr).unwrap();
+                                }
+                                GuiEvent::Position(pos) => {
                                     let msg_buf =
                                         encoder::encode(&OscPacket::Message(OscMessage {
-                                            addr: "/rms".to_string(),
-                                            args: vec![OscType::String(payload)],
+                                            addr: "/position".to_string(),
+                                            args: vec![OscType::Float(pos)],
                                         }))
                                         .unwrap();
-
                                     sock.send_to(&msg_buf, remote_addr).unwrap();
                                 }
-                                GuiEvent::Position(pos) => {
+                                GuiEvent::Spectrum(data) => {
+                                    let msg_buf = encode_values("/spectrum", data);
+                                    sock.send_to(&msg_buf, remote_addr).unwrap();
+                                }
+                                GuiEvent::ActiveGrains(count) => {
                                     let msg_buf =
                                         encoder::encode(&OscPacket::Message(OscMessage {
-                                            addr: "/position".to_string(),
-                                            args: vec![OscType::Float(pos)],
+                                            addr: "/active_grains".to_string(),
+                                            args: vec![OscType::Int(count as i32)],
+                                        }))
+                                        .unwrap();
+                                    sock.send_to(&msg_buf, remote_addr).unwrap();
+                                }
+                                GuiEvent::Correlation(correlation) => {
+                                    let msg_buf =
+                                        encoder::encode(&OscPacket::Message(OscMessage {
+                                            addr: "/correlation".to_string(),
+                                            args: vec![OscType::Float(correlation)],
                                         }))
                                         .unwrap();
                                     sock.send_to(&msg_buf, remote_addr).unwrap();
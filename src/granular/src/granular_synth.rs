@@ -1,4 +1,4 @@
-use dsp::{core::{Module, SharedBuffer, StereoGenerator}, modules::oscillators::Granulator};
+use dsp::{core::{Module, SampleData, SharedBuffer, StereoGenerator}, modules::{analysis::SpectrumAnalyzer, ops::Mixer, stereo::CorrelationMeter, oscillators::{Granulator, PhaseVocoder, SpectralFreeze}}};
 use ring_channel::*;
 use std::{cell::RefCell, rc::Rc};
 
@@ -6,53 +6,150 @@ use crate::event::{SynthEvent, SynthEventReceiver};
 
 pub struct SynthState {
     pub index: f32,
+    // Spectrum of the output in dB, when a new frame is available.
+    pub spectrum: Option<Vec<f32>>,
+    // Playing grains.
+    pub active_grains: usize,
+    // Phase correlation of the output, -1..1
+    pub correlation: f32,
+    // Samples replaced by a new sound, freed by the receiver instead of the audio thread.
+    pub released_samples: Option<[SampleData; 3]>,
 }
 
 const STATE_COUNT: u16 = 50;
+const GRANULAR_CHANNEL: usize = 0;
+const SPECTRAL_CHANNEL: usize = 1;
+const VOCODER_CHANNEL: usize = 2;
 // =========================
 // SYNTH
 // =========================
 pub struct GranularSynth {
     granular_osc: Rc<RefCell<Granulator>>,
+    spectral: Rc<RefCell<SpectralFreeze>>,
+    vocoder: Rc<RefCell<PhaseVocoder>>,
+    mixer: Rc<RefCell<Mixer>>,
+    analyzer: Rc<RefCell<SpectrumAnalyzer>>,
+    correlation: Rc<RefCell<CorrelationMeter>>,
+    spectral_mode: bool,
+    vocoder_mode: bool,
 
     output: Rc<RefCell<dyn StereoGenerator>>,
+    // All the modules, they all follow the sample rate.
     all: Vec<Rc<RefCell<dyn Module>>>,
     event_receiver: SynthEventReceiver,
     state_sender: RingSender<SynthState>,
     state_count: u16,
+    released_samples: Option<[SampleData; 3]>,
 }
 
 impl GranularSynth {
     pub fn new(recv: SynthEventReceiver, state_sender: RingSender<SynthState>) -> Self {
         let granular_osc = Rc::new(RefCell::new(Granulator::new()));
-        let all: Vec<Rc<RefCell<dyn Module>>> = vec![granular_osc.clone()];
+        let spectral = Rc::new(RefCell::new(SpectralFreeze::new()));
+        let vocoder = Rc::new(RefCell::new(PhaseVocoder::new()));
+        let mixer = Rc::new(RefCell::new(Mixer::new(3, 0)));
+        let analyzer = Rc::new(RefCell::new(SpectrumAnalyzer::new()));
+        let correlation = Rc::new(RefCell::new(CorrelationMeter::new()));
+        {
+            let mut m = mixer.try_borrow_mut().unwrap();
+            let g = granular_osc.try_borrow().unwrap();
+            let s = spectral.try_borrow().unwrap();
+            let v = vocoder.try_borrow().unwrap();
+            m.set_channel_stereo_input(GRANULAR_CHANNEL, g.get_left_output(), g.get_right_output());
+            m.set_channel_stereo_input(SPECTRAL_CHANNEL, s.get_left_output(), s.get_right_output());
+            m.set_channel_stereo_input(VOCODER_CHANNEL, v.get_left_output(), v.get_right_output());
+            m.set_channel_mute(SPECTRAL_CHANNEL, true);
+            m.set_channel_mute(VOCODER_CHANNEL, true);
 
+            let mut a = analyzer.try_borrow_mut().unwrap();
+            a.set_left_input(m.get_left_output());
+            a.set_right_input(m.get_right_output());
+
+            let mut c = correlation.try_borrow_mut().unwrap();
+            c.set_left_input(m.get_left_output());
+            c.set_right_input(m.get_right_output());
+        }
+
+        let all: Vec<Rc<RefCell<dyn Module>>> = vec![
+            granular_osc.clone(),
+            spectral.clone(),
+            vocoder.clone(),
+            mixer.clone(),
+            analyzer.clone(),
+            correlation.clone(),
+        ];
         Self {
-            output: granular_osc.clone(),
+            output: mixer.clone(),
             granular_osc,
+            spectral,
+            vocoder,
+            mixer,
+            analyzer,
+            correlation,
+            spectral_mode: false,
+            vocoder_mode: false,
             all,
             event_receiver: recv,
             state_sender,
             state_count: STATE_COUNT,
+            released_samples: None,
         }
     }
 
+    // The granular engine plays when neither mode is on.
+    fn set_engine_modes(&mut self, spectral_mode: bool, vocoder_mode: bool) {
+        self.spectral_mode = spectral_mode;
+        self.vocoder_mode = vocoder_mode;
+        let mut mixer = self.mixer.try_borrow_mut().unwrap();
+        mixer.set_channel_mute(GRANULAR_CHANNEL, spectral_mode || vocoder_mode);
+        mixer.set_channel_mute(SPECTRAL_CHANNEL, !spectral_mode);
+        mixer.set_channel_mute(VOCODER_CHANNEL, !vocoder_mode);
+    }
+
     pub fn handle_event(&mut self) {
         if let Some(event) = self.event_receiver.receive() {
+            // One mode turns the other off.
+            match event {
+                SynthEvent::SpectralMode(spectral_mode) => {
+                    self.set_engine_modes(spectral_mode, self.vocoder_mode && !spectral_mode);
+                    return;
+                }
+                SynthEvent::VocoderMode(vocoder_mode) => {
+                    self.set_engine_modes(self.spectral_mode && !vocoder_mode, vocoder_mode);
+                    return;
+                }
+                _ => {}
+            }
+
             let mut granular = self.granular_osc.try_borrow_mut().unwrap();
+            let mut spectral = self.spectral.try_borrow_mut().unwrap();
+            let mut vocoder = self.vocoder.try_borrow_mut().unwrap();
 
             match event {
                 SynthEvent::LoadSound(samples) => {
-                    granular.load_samples(samples);
+                    self.released_samples = Some([
+                        granular.load_samples(samples.clone()),
+                        spectral.load_samples(samples.clone()),
+                        vocoder.load_samples(samples),
+                    ]);
+                    // Sent with the state of this chunk: one event is handled per chunk,
+                    // the next sound cannot replace these samples before.
+                    self.state_count = 1;
                 }
                 SynthEvent::MainLevel(level) => {
                     granular.set_level(level);
+                    spectral.set_level(level);
+                    vocoder.set_level(level);
                 }
                 SynthEvent::Start(start) => {
                     granular.set_start(start);
+                    spectral.set_start(start);
+                    vocoder.set_start(start);
                 }
                 SynthEvent::End(end) => {
                     granular.set_end(end);
+                    spectral.set_end(end);
+                    vocoder.set_end(end);
                 }
                 SynthEvent::Step(step) => {
                     granular.set_step(step);
@@ -63,17 +160,97 @@ impl GranularSynth {
                 SynthEvent::ScanSpread(spread) => {
                     granular.set_scan_spread(spread);
                 }
+                SynthEvent::ScanMode(scan_mode) => {
+                    granular.set_scan_mode(scan_mode);
+                }
+                SynthEvent::ScanPosition(position) => {
+                    granular.set_scan_position(position);
+                }
                 SynthEvent::GrainStep(step) => {
                     granular.set_grain_step(step);
                 }
                 SynthEvent::GrainsPerSec(grains_per_sec) => {
                     granular.set_grains_per_sec(grains_per_sec);
                 }
+                SynthEvent::GrainScheduling(scheduling) => {
+                    granular.set_grain_scheduling(scheduling);
+                }
+                SynthEvent::GrainJitter(jitter) => {
+                    granular.set_grain_jitter(jitter);
+                }
                 SynthEvent::GrainEnvelop(attack_slope,sustain_duration,release_slope) => {
                     granular.set_grain_attack_slope(attack_slope);
                     granular.set_grain_sustain_duration(sustain_duration);
                     granular.set_grain_release_slope(release_slope);
                 }
+                SynthEvent::GrainWindow(window) => {
+                    granular.set_grain_window(window);
+                }
+                SynthEvent::GrainPoolSize(pool_size) => {
+                    granular.set_grain_pool_size(pool_size);
+                }
+                SynthEvent::StealingPolicy(policy) => {
+                    granular.set_stealing_policy(policy);
+                }
+                SynthEvent::GrainPitchSpread(semi_tones, quantization) => {
+                    granular.set_grain_pitch_spread(semi_tones, quantization);
+                }
+                SynthEvent::GrainGainSpread(spread) => {
+                    granular.set_grain_gain_spread(spread);
+                }
+                SynthEvent::GrainDurationSpread(spread) => {
+                    granular.set_grain_duration_spread(spread);
+                }
+                SynthEvent::GrainReverseProbability(probability) => {
+                    granular.set_grain_reverse_probability(probability);
+                }
+                SynthEvent::SpectralMode(_) | SynthEvent::VocoderMode(_) => {}
+                SynthEvent::SpectralFreeze(frozen) => {
+                    spectral.set_freeze(frozen);
+                }
+                SynthEvent::SpectralCapture => {
+                    spectral.capture();
+                }
+                SynthEvent::SpectralPhaseRandomization(amount) => {
+                    spectral.set_phase_randomization(amount);
+                }
+                SynthEvent::SpectralBinDensity(density) => {
+                    spectral.set_bin_density(density);
+                }
+                SynthEvent::SpectralBlur(blur) => {
+                    spectral.set_blur(blur);
+                }
+                SynthEvent::SpectralSmear(smear) => {
+                    spectral.set_smear(smear);
+                }
+                SynthEvent::VocoderTimeRatio(ratio) => {
+                    vocoder.set_time_ratio(ratio);
+                }
+                SynthEvent::VocoderPitchRatio(ratio) => {
+                    vocoder.set_pitch_ratio(ratio);
+                }
+                SynthEvent::VocoderPhaseLocking(phase_locking) => {
+                    vocoder.set_phase_locking(phase_locking);
+                }
+                SynthEvent::VocoderTransients(preservation, threshold) => {
+                    vocoder.set_transient_preservation(preservation);
+                    vocoder.set_transient_threshold(threshold);
+                }
+                SynthEvent::LoopCrossfade(length, curve) => {
+                    granular.set_loop_crossfade(length, curve);
+                }
+                SynthEvent::ZeroCrossingSnap(zero_crossing_snap) => {
+                    granular.set_zero_crossing_snap(zero_crossing_snap);
+                }
+                SynthEvent::RegionEdge(edge) => {
+                    granular.set_region_edge(edge);
+                }
+                SynthEvent::ChannelSwap(probability) => {
+                    granular.set_channel_swap(probability);
+                }
+                SynthEvent::BalanceSpread(spread) => {
+                    granular.set_balance_spread(spread);
+                }
             }
         };
     }
@@ -83,8 +260,19 @@ impl GranularSynth {
         if self.state_count == 0 {
             self.state_count = STATE_COUNT;
             let granular = self.granular_osc.try_borrow_mut().unwrap();
+            let mut analyzer = self.analyzer.try_borrow_mut().unwrap();
+            // The vocoder plays from its own position.
+            let index = if self.vocoder_mode {
+                self.vocoder.try_borrow().unwrap().current_index()
+            } else {
+                granular.current_index()
+            };
             let _ = self.state_sender.send(SynthState {
-                index: granular.current_index(),
+                index,
+                spectrum: analyzer.take_frame(),
+                active_grains: granular.active_grain_count(),
+                correlation: self.correlation.try_borrow().unwrap().correlation(),
+                released_samples: self.released_samples.take(),
             });
         }
     }
@@ -95,10 +283,23 @@ impl Module for GranularSynth {
         // Handle events
         self.handle_event();
 
-        // Process all
-        self.all
-            .iter()
-            .for_each(|m| m.try_borrow_mut().unwrap().process());
+        // The spectral engine follows the granular scan position.
+        if self.spectral_mode {
+            let position = self.granular_osc.try_borrow().unwrap().current_index();
+            self.spectral.try_borrow_mut().unwrap().set_position(position);
+        }
+
+        // The spectral engine and the vocoder are only processed when their mode is on.
+        self.granular_osc.try_borrow_mut().unwrap().process();
+        if self.spectral_mode {
+            self.spectral.try_borrow_mut().unwrap().process();
+        }
+        if self.vocoder_mode {
+            self.vocoder.try_borrow_mut().unwrap().process();
+        }
+        self.mixer.try_borrow_mut().unwrap().process();
+        self.analyzer.try_borrow_mut().unwrap().process();
+        self.correlation.try_borrow_mut().unwrap().process();
 
         self.send_state();
     }